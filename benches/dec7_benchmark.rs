@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+#[allow(dead_code)]
+#[path = "../src/dec7/mod.rs"]
+mod dec7;
+
+use dec7::{Equation, PART_TWO};
+
+// Half of the equations are solvable by construction, the other half almost surely are not,
+// which forces the forward search to explore the whole tree.
+fn generate_equations(rng: &mut StdRng, length: usize, count: usize) -> Vec<Equation> {
+    let mut equations = Vec::with_capacity(count);
+    while equations.len() < count {
+        let operands: Vec<usize> = (0..length).map(|_| rng.gen_range(1..100)).collect();
+        let target = operands[1..].iter().try_fold(operands[0], |curr, &operand| {
+            match rng.gen_range(0..4) {
+                0 => curr.checked_add(operand),
+                1 => curr.checked_mul(operand),
+                _ if curr < 1_000_000 => format!("{}{}", curr, operand).parse().ok(),
+                _ => curr.checked_add(operand),
+            }
+        });
+        if let Some(target) = target {
+            let target = if equations.len() % 2 == 0 { target } else { target + 1 };
+            equations.push(Equation { target, operands });
+        }
+    }
+    equations
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut group = c.benchmark_group("feasible equations");
+    for length in [6, 9, 12] {
+        let equations = generate_equations(&mut rng, length, 20);
+        group.bench_with_input(BenchmarkId::new("forward", length), &equations, |b, equations| {
+            b.iter(|| equations.iter().filter(|e| e.is_feasible(PART_TWO)).count())
+        });
+        group.bench_with_input(BenchmarkId::new("reverse", length), &equations, |b, equations| {
+            b.iter(|| equations.iter().filter(|e| e.is_feasible_reverse(PART_TWO)).count())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
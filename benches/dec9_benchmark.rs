@@ -0,0 +1,78 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+#[allow(dead_code, unused_imports)]
+#[path = "../src/dec9/mod.rs"]
+mod dec9;
+
+fn generate_disk_map(rng: &mut StdRng, digits: usize) -> String {
+    (0..digits)
+        .map(|i| {
+            let n = if i % 2 == 0 { rng.gen_range(1..10) } else { rng.gen_range(0..10) };
+            char::from_digit(n, 10).unwrap_or('0')
+        })
+        .collect()
+}
+
+// One min-heap of free span positions per span size. The leftmost span that fits a file is the
+// smallest top among the heaps of sizes at least as big as the file.
+fn unfragmented_checksum_heaps(file: &str) -> usize {
+    let mut spaces: [BinaryHeap<Reverse<usize>>; 10] = Default::default();
+    let mut files = Vec::with_capacity(file.len() / 2 + 1);
+    file.chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|n| n as usize)
+        .enumerate()
+        .fold(0, |pos, (i, n)| {
+            if i % 2 == 0 {
+                files.push((pos, n));
+            } else if n > 0 {
+                spaces[n].push(Reverse(pos));
+            }
+            pos + n
+        });
+    files
+        .into_iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, (_, n))| *n > 0)
+        .map(|(id, (pos, n))| {
+            let space = (n..10)
+                .filter_map(|size| spaces[size].peek().map(|&Reverse(space_pos)| (space_pos, size)))
+                .min()
+                .filter(|&(space_pos, _)| space_pos < pos);
+            let new_pos = match space {
+                Some((space_pos, size)) => {
+                    spaces[size].pop();
+                    if size > n {
+                        spaces[size - n].push(Reverse(space_pos + n));
+                    }
+                    space_pos
+                }
+                None => pos,
+            };
+            ((2 * new_pos + n - 1) * n * id) / 2
+        })
+        .sum()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(9);
+    let mut group = c.benchmark_group("unfragmented checksum");
+    group.sample_size(10);
+    for digits in [20_000, 1_000_000, 2_000_000] {
+        let disk_map = generate_disk_map(&mut rng, digits);
+        assert_eq!(unfragmented_checksum_heaps(&disk_map), dec9::unfragmented_checksum(&disk_map));
+        group.bench_with_input(BenchmarkId::new("pointers", digits), &disk_map, |b, disk_map| {
+            b.iter(|| dec9::unfragmented_checksum(disk_map))
+        });
+        group.bench_with_input(BenchmarkId::new("heaps", digits), &disk_map, |b, disk_map| {
+            b.iter(|| unfragmented_checksum_heaps(disk_map))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
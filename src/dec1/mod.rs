@@ -1,42 +1,453 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fs,
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    fmt,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    mem,
+    path::Path,
 };
 
 const PATH: &str = "src/dec1/input.txt";
 
-pub(crate) fn diff() -> i32 {
-    let bytes = fs::read(PATH).unwrap();
-    let mut firsts = vec![];
-    let mut seconds = vec![];
-    let content = String::from_utf8(bytes).unwrap();
-    content.lines().for_each(|line| {
-        let mut words = line.split_ascii_whitespace();
-        firsts.push(words.next().unwrap().parse::<i32>().unwrap());
-        seconds.push(words.next().unwrap().parse::<i32>().unwrap());
-    });
-    firsts.sort_unstable();
-    seconds.sort_unstable();
-    firsts
-        .iter()
-        .zip(seconds)
-        .fold(0, |acc, (first, second)| acc + (first - second).abs())
-}
-
-pub(crate) fn similarity_score() -> i32 {
-    let bytes = fs::read(PATH).unwrap();
-    let mut firsts = HashSet::new();
-    let mut seconds = HashMap::new();
-    let content = String::from_utf8(bytes).unwrap();
-    content.lines().for_each(|line| {
-        let mut words = line.split_ascii_whitespace();
-        firsts.insert(words.next().unwrap().parse::<i32>().unwrap());
-        seconds
-            .entry(words.next().unwrap().parse::<i32>().unwrap())
-            .and_modify(|v| *v += 1)
-            .or_insert(1);
-    });
-    firsts
-        .iter()
-        .fold(0, |acc, curr| acc + *curr * seconds.get(curr).unwrap_or(&0))
+pub(crate) fn diff() -> anyhow::Result<u64> {
+    Ok(read_location_lists()?.total_distance())
+}
+
+pub(crate) fn similarity_score() -> anyhow::Result<u64> {
+    Ok(read_location_lists()?.similarity_score(DuplicateWeight::Each))
+}
+
+pub(crate) fn read_location_lists() -> anyhow::Result<LocationLists> {
+    Ok(LocationLists::parse(&fs::read(PATH)?)?)
+}
+
+// How often a location id repeated in the left list adds to the similarity score. The puzzle
+// counts every occurrence, the first version of this solution counted each id once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DuplicateWeight {
+    Each,
+    Once,
+}
+
+// Lines count from 1.
+#[derive(Debug, PartialEq)]
+pub(crate) enum LocationError {
+    MissingId { line: usize },
+    ExtraId { line: usize },
+    InvalidByte { line: usize, byte: u8 },
+    Overflow { line: usize },
+}
+
+impl fmt::Display for LocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocationError::MissingId { line } => write!(f, "Line {} has less than two ids", line),
+            LocationError::ExtraId { line } => write!(f, "Line {} has more than two ids", line),
+            LocationError::InvalidByte { line, byte } => {
+                write!(f, "Invalid byte {:?} on line {}", *byte as char, line)
+            }
+            LocationError::Overflow { line } => write!(f, "Id too large on line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for LocationError {}
+
+// Both lists are kept sorted, since neither part depends on their order.
+pub(crate) struct LocationLists {
+    left: Vec<u32>,
+    right: Vec<u32>,
+}
+
+impl LocationLists {
+    // Ids are read straight from the bytes, one pair per line, separated by spaces or tabs.
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self, LocationError> {
+        let (mut left, mut right) = (vec![], vec![]);
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        let lines = bytes
+            .split(|&byte| byte == b'\n')
+            .filter(|_| !bytes.is_empty());
+        for (i, line) in lines.enumerate() {
+            let [first, second] = parse_line(i + 1, line)?;
+            left.push(first);
+            right.push(second);
+        }
+        left.sort_unstable();
+        right.sort_unstable();
+        Ok(LocationLists { left, right })
+    }
+
+    pub(crate) fn total_distance(&self) -> u64 {
+        self.left
+            .iter()
+            .zip(&self.right)
+            .map(|(first, second)| first.abs_diff(*second) as u64)
+            .sum()
+    }
+
+    // Walks both sorted lists together, matching each run of equal ids on the left with the run
+    // of the same id on the right.
+    pub(crate) fn similarity_score(&self, weight: DuplicateWeight) -> u64 {
+        let (mut score, mut j) = (0, 0);
+        for (id, occurrences) in runs(&self.left) {
+            while j < self.right.len() && self.right[j] < id {
+                j += 1;
+            }
+            let start = j;
+            while j < self.right.len() && self.right[j] == id {
+                j += 1;
+            }
+            let weight = match weight {
+                DuplicateWeight::Each => occurrences,
+                DuplicateWeight::Once => 1,
+            };
+            score += id as u64 * (j - start) as u64 * weight as u64;
+        }
+        score
+    }
+}
+
+// Parses the pair of ids on a line, which may still end in `\r`.
+fn parse_line(line_number: usize, line: &[u8]) -> Result<[u32; 2], LocationError> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let mut ids = [0; 2];
+    let mut count = 0;
+    let mut current: Option<u32> = None;
+    for &byte in line.iter().chain([b' '].iter()) {
+        match byte {
+            b'0'..=b'9' => {
+                let digit = (byte - b'0') as u32;
+                current = Some(
+                    current
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|id| id.checked_add(digit))
+                        .ok_or(LocationError::Overflow { line: line_number })?,
+                );
+            }
+            b' ' | b'\t' => {
+                if let Some(id) = current.take() {
+                    if count == 2 {
+                        return Err(LocationError::ExtraId { line: line_number });
+                    }
+                    ids[count] = id;
+                    count += 1;
+                }
+            }
+            byte => {
+                return Err(LocationError::InvalidByte {
+                    line: line_number,
+                    byte,
+                })
+            }
+        }
+    }
+    if count < 2 {
+        return Err(LocationError::MissingId { line: line_number });
+    }
+    Ok(ids)
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct StreamingTotals {
+    pub(crate) total_distance: u64,
+    pub(crate) similarity_score: u64,
+}
+
+pub(crate) fn stream_location_lists(
+    path: &Path,
+    chunk_size: usize,
+) -> anyhow::Result<StreamingTotals> {
+    stream_totals(BufReader::new(File::open(path)?), chunk_size)
+}
+
+// For lists that don't fit in memory. Every `chunk_size` lines both chunks are sorted and spilled
+// to temporary files, which are merged back into a single sorted file per list. Reading both
+// files together pairs up the ids for the distance, and reading them again, matching each run of
+// equal ids on the left with the same run on the right, gives the similarity score, counting
+// every occurrence on the left like `similarity_score`.
+pub(crate) fn stream_totals<R: BufRead>(
+    mut reader: R,
+    chunk_size: usize,
+) -> anyhow::Result<StreamingTotals> {
+    let chunk_size = chunk_size.max(1);
+    let (mut left_runs, mut right_runs) = (SortedRuns::default(), SortedRuns::default());
+    let (mut left, mut right) = (
+        Vec::with_capacity(chunk_size),
+        Vec::with_capacity(chunk_size),
+    );
+    let mut line = vec![];
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let [first, second] = parse_line(line_number, line.strip_suffix(b"\n").unwrap_or(&line))?;
+        left.push(first);
+        right.push(second);
+        if left.len() == chunk_size {
+            left_runs.push(spill(&mut left)?)?;
+            right_runs.push(spill(&mut right)?)?;
+        }
+    }
+    if !left.is_empty() {
+        left_runs.push(spill(&mut left)?)?;
+        right_runs.push(spill(&mut right)?)?;
+    }
+    let (mut left_ids, mut right_ids) = (left_runs.finish()?, right_runs.finish()?);
+    let total_distance = read_ids(&mut left_ids)
+        .zip(read_ids(&mut right_ids))
+        .try_fold(0, |total, (first, second)| {
+            anyhow::Ok(total + first?.abs_diff(second?) as u64)
+        })?;
+    left_ids.rewind()?;
+    right_ids.rewind()?;
+    let (mut left_counts, mut right_counts) = (count_ids(left_ids), count_ids(right_ids));
+    let (mut first, mut second) = (
+        left_counts.next().transpose()?,
+        right_counts.next().transpose()?,
+    );
+    let mut similarity_score = 0;
+    while let (Some((left_id, left_count)), Some((right_id, right_count))) = (first, second) {
+        match left_id.cmp(&right_id) {
+            Ordering::Less => first = left_counts.next().transpose()?,
+            Ordering::Greater => second = right_counts.next().transpose()?,
+            Ordering::Equal => {
+                similarity_score += left_id as u64 * left_count * right_count;
+                first = left_counts.next().transpose()?;
+                second = right_counts.next().transpose()?;
+            }
+        }
+    }
+    Ok(StreamingTotals {
+        total_distance,
+        similarity_score,
+    })
+}
+
+// At most this many runs are merged at once, so only a few files per level stay open.
+const MAX_FAN_IN: usize = 16;
+
+// The sorted runs of one list, by level. A level filling up with `MAX_FAN_IN` runs is merged
+// into a single run of the next level, so every id is written once per level.
+#[derive(Default)]
+struct SortedRuns {
+    levels: Vec<Vec<BufReader<File>>>,
+}
+
+impl SortedRuns {
+    fn push(&mut self, mut run: BufReader<File>) -> anyhow::Result<()> {
+        for level in 0.. {
+            if level == self.levels.len() {
+                self.levels.push(vec![]);
+            }
+            self.levels[level].push(run);
+            if self.levels[level].len() < MAX_FAN_IN {
+                break;
+            }
+            run = write_run(merge(mem::take(&mut self.levels[level]))?)?;
+        }
+        Ok(())
+    }
+
+    // Merges the levels from the lowest up, carrying the merged run of each level to the next.
+    fn finish(self) -> anyhow::Result<BufReader<File>> {
+        let mut merged = None;
+        for mut runs in self.levels {
+            runs.extend(merged);
+            merged = Some(write_run(merge(runs)?)?);
+        }
+        merged.map_or_else(|| write_run(std::iter::empty()), anyhow::Ok)
+    }
+}
+
+// Sorts the chunk into a temporary file and empties it.
+fn spill(chunk: &mut Vec<u32>) -> anyhow::Result<BufReader<File>> {
+    chunk.sort_unstable();
+    write_run(chunk.drain(..).map(anyhow::Ok))
+}
+
+// Writes the ids to a temporary file, as little endian, ready to be read back from the start.
+fn write_run(ids: impl Iterator<Item = anyhow::Result<u32>>) -> anyhow::Result<BufReader<File>> {
+    let mut file = BufWriter::new(tempfile::tempfile()?);
+    for id in ids {
+        file.write_all(&id?.to_le_bytes())?;
+    }
+    let mut file = file.into_inner()?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(BufReader::new(file))
+}
+
+// Merges the sorted runs by always taking the smallest of their next ids.
+fn merge(
+    mut runs: Vec<BufReader<File>>,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<u32>>> {
+    let mut heads = BinaryHeap::new();
+    for (i, run) in runs.iter_mut().enumerate() {
+        if let Some(id) = read_id(run)? {
+            heads.push(Reverse((id, i)));
+        }
+    }
+    Ok(std::iter::from_fn(move || {
+        let Reverse((id, i)) = heads.pop()?;
+        match read_id(&mut runs[i]) {
+            Ok(Some(next)) => heads.push(Reverse((next, i))),
+            Ok(None) => (),
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Ok(id))
+    }))
+}
+
+fn read_ids(run: &mut BufReader<File>) -> impl Iterator<Item = anyhow::Result<u32>> + '_ {
+    std::iter::from_fn(|| read_id(run).transpose())
+}
+
+// Each distinct id of a sorted run with its number of occurrences.
+fn count_ids(mut run: BufReader<File>) -> impl Iterator<Item = anyhow::Result<(u32, u64)>> {
+    let mut pending = None;
+    std::iter::from_fn(move || {
+        let id = match pending.take() {
+            Some(id) => id,
+            None => match read_id(&mut run) {
+                Ok(id) => id?,
+                Err(e) => return Some(Err(e)),
+            },
+        };
+        let mut count = 1;
+        loop {
+            match read_id(&mut run) {
+                Ok(Some(next)) if next == id => count += 1,
+                Ok(next) => {
+                    pending = next;
+                    return Some(Ok((id, count)));
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    })
+}
+
+fn read_id(run: &mut BufReader<File>) -> anyhow::Result<Option<u32>> {
+    let mut bytes = [0; 4];
+    match run.read_exact(&mut bytes) {
+        Ok(()) => Ok(Some(u32::from_le_bytes(bytes))),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Each distinct id of a sorted list with its number of occurrences.
+fn runs(ids: &[u32]) -> impl Iterator<Item = (u32, usize)> + '_ {
+    ids.chunk_by(|a, b| a == b).map(|run| (run[0], run.len()))
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    const EXAMPLE: &str = "\
+3   4
+4   3
+2   5
+1   3
+3   9
+3   3
+";
+
+    #[test]
+    fn test_example() -> anyhow::Result<()> {
+        let lists = LocationLists::parse(EXAMPLE.as_bytes())?;
+        assert_eq!(lists.total_distance(), 11);
+        assert_eq!(lists.similarity_score(DuplicateWeight::Each), 31);
+        assert_eq!(lists.similarity_score(DuplicateWeight::Once), 9 + 4);
+        let windows = LocationLists::parse(EXAMPLE.replace('\n', "\r\n").as_bytes())?;
+        assert_eq!(windows.total_distance(), 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_lines() {
+        let error = |contents: &str| LocationLists::parse(contents.as_bytes()).err();
+        assert_eq!(
+            error("1   2\n3\n"),
+            Some(LocationError::MissingId { line: 2 })
+        );
+        assert_eq!(
+            error("1   2\n\n3   4"),
+            Some(LocationError::MissingId { line: 2 })
+        );
+        assert_eq!(error("1 2 3"), Some(LocationError::ExtraId { line: 1 }));
+        assert_eq!(
+            error("1   2\n3   -4\n"),
+            Some(LocationError::InvalidByte {
+                line: 2,
+                byte: b'-'
+            })
+        );
+        assert_eq!(
+            error("1   99999999999"),
+            Some(LocationError::Overflow { line: 1 })
+        );
+        assert_eq!(error(""), None);
+    }
+
+    #[test]
+    fn test_streaming() -> anyhow::Result<()> {
+        let expected = StreamingTotals {
+            total_distance: 11,
+            similarity_score: 31,
+        };
+        for chunk_size in [1, 2, 4, 6, 100] {
+            assert_eq!(stream_totals(EXAMPLE.as_bytes(), chunk_size)?, expected);
+        }
+        assert_eq!(
+            stream_totals("1   2\n3   x\n".as_bytes(), 1)
+                .err()
+                .map(|e| e.to_string()),
+            Some("Invalid byte 'x' on line 2".to_string())
+        );
+        let mut rng = StdRng::seed_from_u64(2024);
+        let generated = (0..5000)
+            .map(|_| format!("{}   {}", rng.gen_range(0..300), rng.gen_range(0..300)))
+            .join("\n");
+        let lists = LocationLists::parse(generated.as_bytes())?;
+        let streamed = stream_totals(generated.as_bytes(), 333)?;
+        assert_eq!(streamed.total_distance, lists.total_distance());
+        assert_eq!(
+            streamed.similarity_score,
+            lists.similarity_score(DuplicateWeight::Each)
+        );
+        // One run per line, far more than can be merged, or kept open, at once.
+        let streamed = stream_totals(generated.as_bytes(), 1)?;
+        assert_eq!(streamed.total_distance, lists.total_distance());
+        assert_eq!(
+            streamed.similarity_score,
+            lists.similarity_score(DuplicateWeight::Each)
+        );
+        assert_eq!(
+            stream_totals("".as_bytes(), 1)?,
+            StreamingTotals {
+                total_distance: 0,
+                similarity_score: 0,
+            }
+        );
+        let streamed = stream_location_lists(Path::new(PATH), 100)?;
+        assert_eq!(streamed.total_distance, diff()?);
+        assert_eq!(streamed.similarity_score, similarity_score()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_input() -> anyhow::Result<()> {
+        assert_eq!(diff()?, 3246517);
+        assert_eq!(similarity_score()?, 29379307);
+        Ok(())
+    }
 }
@@ -1,56 +1,313 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader, Write}, path::Path,
+    borrow::Cow,
+    collections::HashSet,
+    fs::{self, File},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufRead, BufReader, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
-use anyhow::Error;
+use anyhow::{bail, Context};
+use gif::{Encoder, Frame, Repeat};
+use itertools::Itertools;
+use num_integer::{gcd, lcm};
 use regex::Regex;
-use rayon::prelude::*;
 
 const PATH: &str = "src/dec14/input.txt";
 
-pub(crate) fn safety_factor() -> anyhow::Result<isize> {
-    let file = File::open(PATH)?;
-    let contents = BufReader::new(file);
-    contents
+pub(crate) const ROOM: Room = Room {
+    width: 101,
+    height: 103,
+};
+
+pub(crate) fn safety_factor() -> anyhow::Result<usize> {
+    let robots = read_robots(BufReader::new(File::open(PATH)?))?;
+    Ok(ROOM.simulate(&robots, 100).safety_factor())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Room {
+    pub(crate) width: isize,
+    pub(crate) height: isize,
+}
+
+// Quadrants are numbered left to right, top to bottom. Robots on a middle row or column, which
+// only exist for odd sizes, belong to none.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Simulation {
+    pub(crate) positions: Vec<(isize, isize)>,
+    pub(crate) quadrants: [usize; 4],
+}
+
+impl Simulation {
+    pub(crate) fn safety_factor(&self) -> usize {
+        self.quadrants.iter().product()
+    }
+}
+
+impl Room {
+    pub(crate) fn simulate(&self, robots: &[Robot], seconds: isize) -> Simulation {
+        let mut quadrants = [0; 4];
+        let positions = robots
+            .iter()
+            .map(|robot| {
+                let mut robot = robot.clone();
+                robot.move_times(self, seconds);
+                if let Some(quadrant) = self.quadrant(robot.x, robot.y) {
+                    quadrants[quadrant] += 1;
+                }
+                (robot.x, robot.y)
+            })
+            .collect();
+        Simulation {
+            positions,
+            quadrants,
+        }
+    }
+
+    fn quadrant(&self, x: isize, y: isize) -> Option<usize> {
+        let column = match x {
+            x if x < self.width / 2 => 0,
+            x if x >= (self.width + 1) / 2 => 1,
+            _ => return None,
+        };
+        let row = match y {
+            y if y < self.height / 2 => 0,
+            y if y >= (self.height + 1) / 2 => 1,
+            _ => return None,
+        };
+        Some(row * 2 + column)
+    }
+}
+
+pub(crate) fn read_robots<R: BufRead>(content: R) -> anyhow::Result<Vec<Robot>> {
+    content
         .lines()
-        .try_fold([0, 0, 0, 0], |mut acc, line| {
-            let mut robot = Robot::from(&line?[..]);
-            robot.move_times(100);
-            if let Some(quadrant) = robot.get_cuadrant() {
-                acc[quadrant] += 1;
+        .map(|line| Ok(Robot::from(&line?[..])))
+        .collect()
+}
+
+// The picture gathers most robots in a small area, so it shows up as the second with the least
+// spread. Both detectors only need to look at one full period of the room.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Detector {
+    // Each axis repeats on its own, every width or height seconds, so the seconds with the least
+    // variance on each axis are combined by the chinese remainder theorem.
+    Variance,
+    // Most robots end up in a single quadrant.
+    SafetyFactor,
+}
+
+pub(crate) struct EasterEgg {
+    pub(crate) second: isize,
+    pub(crate) frame: String,
+}
+
+pub(crate) fn find_easter_egg(detector: Detector) -> anyhow::Result<EasterEgg> {
+    let robots = read_robots(BufReader::new(File::open(PATH)?))?;
+    ROOM.find_easter_egg(&robots, detector)
+}
+
+impl Room {
+    pub(crate) fn find_easter_egg(
+        &self,
+        robots: &[Robot],
+        detector: Detector,
+    ) -> anyhow::Result<EasterEgg> {
+        let second = match detector {
+            Detector::Variance => {
+                let x_second = self.least_variance_second(robots, self.width, |(x, _)| x);
+                let y_second = self.least_variance_second(robots, self.height, |(_, y)| y);
+                (0..self.height)
+                    .map(|k| x_second + k * self.width)
+                    .find(|second| second % self.height == y_second)
+                    .filter(|_| gcd(self.width, self.height) == 1)
+                    .context("Room sizes are not coprime")?
             }
-            Ok::<[isize; 4], Error>(acc)
+            Detector::SafetyFactor => (0..self.width * self.height)
+                .min_by_key(|&second| self.simulate(robots, second).safety_factor())
+                .context("Room is empty")?,
+        };
+        Ok(EasterEgg {
+            second,
+            frame: self.render(&self.simulate(robots, second).positions),
         })
-        .map(|result| result.iter().product())
-}
-
-fn render_tree(moves: isize) -> anyhow::Result<()> {
-    let mut grid = [['.'; 101]; 103];
-    let file = File::open(PATH)?;
-    let contents = BufReader::new(file);
-    contents.lines().try_for_each(|line| {
-        let mut robot = Robot::from(&line?[..]);
-        robot.move_times(moves);
-        let Robot { x, y , ..} = robot;
-        grid[y as usize][x as usize] = '#';
-        Ok::<(), anyhow::Error>(())
-    })?;
-    if grid.iter().any(|line| line.iter().collect::<String>().contains("########")) {} else { return Ok(())};
-    let filepath = format!("src/dec14/outputs/{}.txt", moves);
-    let path = Path::new(&filepath[..]);
-    let mut file = File::create(path)?;
-
-    // Iterate through each inner vector (line)
-    for line in grid {
-        // Convert the line of chars to a string and write it to the file
-        let line_string: String = line.iter().collect();
-        writeln!(file, "{}", line_string)?;
-    }
-    Ok(())
+    }
+
+    fn least_variance_second(
+        &self,
+        robots: &[Robot],
+        period: isize,
+        axis: fn((isize, isize)) -> isize,
+    ) -> isize {
+        (0..period)
+            .min_by_key(|&second| {
+                let values = self
+                    .simulate(robots, second)
+                    .positions
+                    .into_iter()
+                    .map(axis);
+                let (n, sum, squares) = values.fold((0, 0, 0), |(n, sum, squares), value| {
+                    (n + 1, sum + value, squares + value * value)
+                });
+                // Variance scaled by n squared, to stay within integers.
+                n * squares - sum * sum
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn render(&self, positions: &[(isize, isize)]) -> String {
+        let mut grid = vec![vec!['.'; self.width as usize]; self.height as usize];
+        positions
+            .iter()
+            .for_each(|&(x, y)| grid[y as usize][x as usize] = '#');
+        grid.iter()
+            .map(|row| row.iter().collect::<String>())
+            .join("\n")
+    }
 }
+
+// Text frames are concatenated into a single `frames.txt`, PGM frames get a `<second>.pgm` image
+// each and GIF frames make up a single `frames.gif` animation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum FrameFormat {
+    Text,
+    Pgm,
+    Gif,
+}
+
+impl FromStr for FrameFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(FrameFormat::Text),
+            "pgm" => Ok(FrameFormat::Pgm),
+            "gif" => Ok(FrameFormat::Gif),
+            _ => bail!("Unknown frame format {}", s),
+        }
+    }
+}
+
+pub(crate) fn export_frames(
+    seconds: Range<isize>,
+    directory: &Path,
+    format: FrameFormat,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let robots = read_robots(BufReader::new(File::open(PATH)?))?;
+    ROOM.export_frames(&robots, seconds, directory, format)
+}
+
+impl Room {
+    // Returns the paths of the written files. The directory is created if needed.
+    pub(crate) fn export_frames(
+        &self,
+        robots: &[Robot],
+        seconds: Range<isize>,
+        directory: &Path,
+        format: FrameFormat,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        fs::create_dir_all(directory)?;
+        let mut frames = seconds.map(|second| (second, self.simulate(robots, second).positions));
+        match format {
+            FrameFormat::Text => {
+                let path = directory.join("frames.txt");
+                let mut file = BufWriter::new(File::create(&path)?);
+                frames.try_for_each(|(second, positions)| {
+                    writeln!(file, "Second {}\n{}\n", second, self.render(&positions))
+                })?;
+                file.flush()?;
+                Ok(vec![path])
+            }
+            FrameFormat::Pgm => frames
+                .map(|(second, positions)| {
+                    let path = directory.join(format!("{}.pgm", second));
+                    let mut file = BufWriter::new(File::create(&path)?);
+                    write!(file, "P5\n{} {}\n255\n", self.width, self.height)?;
+                    let pixels = self.pixels(&positions);
+                    file.write_all(&pixels.iter().map(|&pixel| pixel * 255).collect_vec())?;
+                    file.flush()?;
+                    Ok(path)
+                })
+                .collect(),
+            FrameFormat::Gif => {
+                let path = directory.join("frames.gif");
+                let (width, height) = (u16::try_from(self.width)?, u16::try_from(self.height)?);
+                let palette = [0, 0, 0, 255, 255, 255];
+                let file = BufWriter::new(File::create(&path)?);
+                let mut encoder = Encoder::new(file, width, height, &palette)?;
+                encoder.set_repeat(Repeat::Infinite)?;
+                frames.try_for_each(|(_, positions)| {
+                    encoder.write_frame(&Frame {
+                        width,
+                        height,
+                        delay: 10,
+                        buffer: Cow::Owned(self.pixels(&positions)),
+                        ..Frame::default()
+                    })
+                })?;
+                Ok(vec![path])
+            }
+        }
+    }
+
+    // One byte per tile, row by row, 1 where there is a robot and 0 elsewhere.
+    fn pixels(&self, positions: &[(isize, isize)]) -> Vec<u8> {
+        let mut pixels = vec![0; (self.width * self.height) as usize];
+        positions
+            .iter()
+            .for_each(|&(x, y)| pixels[(y * self.width + x) as usize] = 1);
+        pixels
+    }
+}
+
+// Everything repeats after the system period, so the overlaps and configurations are only looked
+// for within the first one.
+pub(crate) struct PeriodAnalysis {
+    pub(crate) robot_periods: Vec<isize>,
+    pub(crate) system_period: isize,
+    pub(crate) overlap_seconds: Vec<isize>,
+    pub(crate) distinct_configurations: usize,
+}
+
+pub(crate) fn analyze_periods() -> anyhow::Result<PeriodAnalysis> {
+    let robots = read_robots(BufReader::new(File::open(PATH)?))?;
+    Ok(ROOM.analyze_periods(&robots))
+}
+
+impl Room {
+    pub(crate) fn analyze_periods(&self, robots: &[Robot]) -> PeriodAnalysis {
+        let robot_periods = robots.iter().map(|robot| robot.period(self)).collect_vec();
+        let system_period = robot_periods
+            .iter()
+            .fold(1, |acc, &period| lcm(acc, period));
+        let mut overlap_seconds = vec![];
+        // Keeping every configuration of the input would take tens of megabytes, so only their
+        // hashes are kept.
+        let mut configurations = HashSet::new();
+        (0..system_period).for_each(|second| {
+            let mut positions = self.simulate(robots, second).positions;
+            positions.sort_unstable();
+            if positions.windows(2).any(|pair| pair[0] == pair[1]) {
+                overlap_seconds.push(second);
+            }
+            let mut hasher = DefaultHasher::new();
+            positions.hash(&mut hasher);
+            configurations.insert(hasher.finish());
+        });
+        PeriodAnalysis {
+            robot_periods,
+            system_period,
+            overlap_seconds,
+            distinct_configurations: configurations.len(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
-struct Robot {
+pub(crate) struct Robot {
     x: isize,
     y: isize,
     vx: isize,
@@ -58,20 +315,26 @@ struct Robot {
 }
 
 impl Robot {
-    fn move_times(&mut self, times: isize) {
+    fn move_times(&mut self, room: &Room, times: isize) {
         let Robot { x, y, vx, vy } = self;
-        *x = (((*x + *vx * times) % 101) + 101) % 101;
-        *y = (((*y + *vy * times) % 103) + 103) % 103;
+        *x = (*x + *vx * times).rem_euclid(room.width);
+        *y = (*y + *vy * times).rem_euclid(room.height);
     }
 
-    fn get_cuadrant(&self) -> Option<usize> {
-        match (self.x, self.y) {
-            (x, y) if x < 50 && y < 51 => Some(0),
-            (x, y) if x > 50 && y < 51 => Some(1),
-            (x, y) if x < 50 && y > 51 => Some(2),
-            (x, y) if x > 50 && y > 51 => Some(3),
-            _ => None,
-        }
+    // Each axis comes back to its start after the size divided by how many times the speed wraps.
+    fn period(&self, room: &Room) -> isize {
+        let x_period = room.width / gcd(self.vx, room.width);
+        let y_period = room.height / gcd(self.vy, room.height);
+        let period = lcm(x_period, y_period);
+        debug_assert_eq!(
+            {
+                let mut robot = self.clone();
+                robot.move_times(room, period);
+                robot
+            },
+            *self
+        );
+        period
     }
 }
 impl From<&str> for Robot {
@@ -91,11 +354,138 @@ impl From<&str> for Robot {
 mod test {
     use super::*;
 
+    const EXAMPLE: &str = "\
+p=0,4 v=3,-3
+p=6,3 v=-1,-3
+p=10,3 v=-1,2
+p=2,0 v=2,-1
+p=0,0 v=1,3
+p=3,0 v=-2,-2
+p=7,6 v=-1,-3
+p=3,0 v=-1,-2
+p=9,3 v=2,3
+p=7,3 v=-1,2
+p=2,4 v=2,-3
+p=9,5 v=-3,-3";
+
+    #[test]
+    fn test_example() -> anyhow::Result<()> {
+        let robots = read_robots(EXAMPLE.as_bytes())?;
+        let room = Room {
+            width: 11,
+            height: 7,
+        };
+        let simulation = room.simulate(&robots, 100);
+        assert_eq!(simulation.quadrants, [1, 3, 4, 1]);
+        assert_eq!(simulation.safety_factor(), 12);
+        assert!(simulation.positions.contains(&(6, 0)));
+        assert_eq!(room.simulate(&robots[10..11], 5).positions, vec![(1, 3)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_frames() -> anyhow::Result<()> {
+        let robots = read_robots(EXAMPLE.as_bytes())?;
+        let room = Room {
+            width: 11,
+            height: 7,
+        };
+        let directory = tempfile::tempdir()?;
+        let text = room.export_frames(&robots, 0..3, directory.path(), FrameFormat::Text)?;
+        let content = fs::read_to_string(&text[0])?;
+        assert!(content.starts_with("Second 0\n#.##......."));
+        assert!(content.contains(&format!(
+            "Second 2\n{}\n",
+            room.render(&room.simulate(&robots, 2).positions)
+        )));
+        let images = room.export_frames(&robots, 0..3, directory.path(), FrameFormat::Pgm)?;
+        assert_eq!(images.len(), 3);
+        let image = fs::read(&images[1])?;
+        assert!(image.starts_with(b"P5\n11 7\n255\n"));
+        assert_eq!(image.len(), 12 + 11 * 7);
+        let animation = room.export_frames(&robots, 0..3, directory.path(), FrameFormat::Gif)?;
+        assert!(fs::read(&animation[0])?.starts_with(b"GIF89a"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_periods() -> anyhow::Result<()> {
+        let mut robots = read_robots(EXAMPLE.as_bytes())?;
+        robots.extend(read_robots("p=1,1 v=0,7\np=2,2 v=11,0".as_bytes())?);
+        let room = Room {
+            width: 11,
+            height: 7,
+        };
+        let analysis = room.analyze_periods(&robots);
+        assert_eq!(analysis.robot_periods[..12], [77; 12]);
+        assert_eq!(analysis.robot_periods[12..], [1, 1]);
+        assert_eq!(analysis.system_period, 77);
+        assert_eq!(analysis.overlap_seconds.len(), 58);
+        assert_eq!(analysis.overlap_seconds[0], 0);
+        assert_eq!(analysis.distinct_configurations, 77);
+        Ok(())
+    }
+
+    #[test]
+    fn test_input_periods() -> anyhow::Result<()> {
+        let analysis = analyze_periods()?;
+        assert_eq!(analysis.system_period, 101 * 103);
+        assert_eq!(analysis.distinct_configurations, 101 * 103);
+        // The picture is the only frame where every robot has a tile of its own.
+        assert_eq!(analysis.overlap_seconds.len(), 101 * 103 - 1);
+        assert!(!analysis.overlap_seconds.contains(&7774));
+        Ok(())
+    }
+
+    #[test]
+    fn test_input() -> anyhow::Result<()> {
+        assert_eq!(safety_factor()?, 225521010);
+        Ok(())
+    }
+
+    #[test]
+    fn test_easter_egg() -> anyhow::Result<()> {
+        let by_variance = find_easter_egg(Detector::Variance)?;
+        let by_safety_factor = find_easter_egg(Detector::SafetyFactor)?;
+        assert_eq!(by_variance.second, 7774);
+        assert_eq!(by_safety_factor.second, by_variance.second);
+        let expected = std::fs::read_to_string("src/dec14/outputs/7774.txt")?;
+        assert_eq!(by_variance.frame, expected.trim_end());
+        Ok(())
+    }
+
+    #[test]
+    fn test_easter_egg_errors() -> anyhow::Result<()> {
+        let robots = read_robots(EXAMPLE.as_bytes())?;
+        let error = |room: Room, detector| {
+            room.find_easter_egg(&robots, detector)
+                .err()
+                .map(|e| e.to_string())
+        };
+        let square = Room {
+            width: 4,
+            height: 4,
+        };
+        assert_eq!(
+            error(square, Detector::Variance),
+            Some("Room sizes are not coprime".to_string())
+        );
+        assert_eq!(error(square, Detector::SafetyFactor), None);
+        let empty = Room {
+            width: 0,
+            height: 7,
+        };
+        assert_eq!(
+            error(empty, Detector::SafetyFactor),
+            Some("Room is empty".to_string())
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_parse() {
         let haystack = "p=74,51 v=36,-94";
         let robot = Robot::from(haystack);
-        // (5000..10000).par_bridge().for_each(|i| {render_tree(i).unwrap();});
         assert_eq!(
             robot,
             Robot {
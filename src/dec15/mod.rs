@@ -1,229 +1,497 @@
-use std::fs::read_to_string;
+use std::{collections::VecDeque, fmt, fs::read_to_string};
+
+use itertools::Itertools;
 
 const PATH: &str = "src/dec15/input.txt";
 
 pub(crate) fn sum_box_positions() -> anyhow::Result<usize> {
-    let contents = read_to_string(PATH)?;
-    let mut split = contents.split("\n\n");
-    let grid = split.next().unwrap();
-    let moves = split.next().unwrap();
-    let mut robot = Robot::new();
-    let mut grid: Vec<Vec<char>> = grid
-        .lines()
-        .enumerate()
-        .map(|(i, line)| {
-            line.chars()
-                .enumerate()
-                .map(|(j, position)| {
-                    if position == '@' {
-                        robot.i = i;
-                        robot.j = j;
-                    }
-                    position
-                })
-                .collect()
-        })
-        .collect();
-    moves.lines().for_each(|line| {
-        line.chars().for_each(|direction| {
-            robot.try_move(&mut grid, &Direction::try_from_option(direction).unwrap())
-        })
-    });
-    let result = grid
-        .iter()
-        .enumerate()
-        .map(|(i, row)| {
-            row.iter()
-                .enumerate()
-                .filter(|(_, &position)| position == 'O')
-                .map(|(j, _)| 100 * i + j)
-                .sum::<usize>()
-        })
-        .sum();
-    // let path = Path::new("src/dec15/output.txt");
-    // let mut file = File::create(path)?;
-    // for line in grid {
-    //     let line_string: String = line.iter().collect();
-    //     writeln!(file, "{}", line_string)?;
-    // }
-    Ok(result)
+    let mut warehouse = read_warehouse(1)?;
+    warehouse.run();
+    Ok(warehouse.gps_sum())
 }
 
 pub(crate) fn sum_wide_box_positions() -> anyhow::Result<usize> {
-    let contents = read_to_string(PATH)?;
-    let mut split = contents.split("\n\n");
-    let grid = split.next().unwrap();
-    let moves = split.next().unwrap();
-    let mut robot = Robot::new();
-    let mut grid: Vec<Vec<char>> = grid
-        .lines()
-        .enumerate()
-        .map(|(i, line)| {
-            let mut row = Vec::with_capacity(line.len() * 2);
-            line.chars()
-                .enumerate()
-                .for_each(|(j, position)| match position {
-                    '@' => {
-                        robot.i = i;
-                        robot.j = 2 * j;
-                        row.extend(['@', '.'])
-                    }
-                    'O' => row.extend(['[', ']']),
-                    '#' => row.extend(['#', '#']),
-                    _ => row.extend(['.', '.']),
-                });
-            row
-        })
-        .collect();
-
-    moves.lines().for_each(|line| {
-        line.chars().enumerate().for_each(|direction| {
-            robot.try_move_wide(&mut grid, &Direction::try_from_option(direction.1).unwrap());
-            // let var_name = &format!("src/dec15/output{}.txt", direction.0);
-            // let path = Path::new(&var_name[..]);
-            // let mut file = File::create(path).unwrap();
-            // for line in grid.iter() {
-            //     let line_string: String = line.iter().collect();
-            //     writeln!(file, "{}", line_string).unwrap();
-            // }
-        })
-    });
-    let result = grid
-        .iter()
-        .enumerate()
-        .map(|(i, row)| {
-            row.iter()
-                .enumerate()
-                .filter(|(_, &position)| position == '[')
-                .map(|(j, _)| 100 * i + j)
-                .sum::<usize>()
-        })
-        .sum();
-    // let path = Path::new("src/dec15/output.txt");
-    // let mut file = File::create(path)?;
-    // for line in grid {
-    //     let line_string: String = line.iter().collect();
-    //     writeln!(file, "{}", line_string)?;
-    // }
-    Ok(result)
-}
-
-fn can_move(i: usize, j: usize, direction: &Direction, grid: &[Vec<char>], is_pair: bool) -> bool {
-    match grid[i][j] {
-        '.' => true,
-        ']' => {
-            let (i2, j2) = direction.move_forward(grid, i, j).unwrap();
-            if *direction == Direction::Left || is_pair {
-                return can_move(i2, j2, direction, grid, !is_pair);
-            }
-            can_move(i, j - 1, direction, grid, true) && can_move(i2, j2, direction, grid, false)
-        }
-        '[' => {
-            let (i2, j2) = direction.move_forward(grid, i, j).unwrap();
-            if *direction == Direction::Right || is_pair {
-                return can_move(i2, j2, direction, grid, !is_pair);
-            }
-            can_move(i, j + 1, direction, grid, true) && can_move(i2, j2, direction, grid, false)
+    let mut warehouse = read_warehouse(2)?;
+    warehouse.run();
+    Ok(warehouse.gps_sum())
+}
+
+pub(crate) fn read_warehouse(scale: usize) -> anyhow::Result<Warehouse> {
+    Ok(Warehouse::parse(&read_to_string(PATH)?, scale)?)
+}
+
+// Boxes are identified by the position of their left side before the move.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MoveRecord {
+    pub(crate) direction: Direction,
+    pub(crate) moved: bool,
+    pub(crate) boxes: Vec<(usize, usize)>,
+}
+
+impl fmt::Display for MoveRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.moved { "moved" } else { "blocked" };
+        write!(f, "{} {}", self.direction, outcome)?;
+        if !self.boxes.is_empty() {
+            let boxes = self
+                .boxes
+                .iter()
+                .map(|(i, j)| format!("({},{})", i, j))
+                .join(" ");
+            write!(f, ", pushed {}", boxes)?;
         }
-        _ => false,
+        Ok(())
     }
 }
 
-fn do_move(
-    prev: char,
-    i: usize,
-    j: usize,
-    direction: &Direction,
-    grid: &mut [Vec<char>],
-    is_pair: bool,
-) {
-    match grid[i][j] {
-        ']' => {
-            let (i2, j2) = direction.move_forward(grid, i, j).unwrap();
-            if *direction == Direction::Left || is_pair {
-                do_move(']', i2, j2, direction, grid, !is_pair)
-            } else {
-                do_move('.', i, j - 1, direction, grid, true);
-                do_move(']', i2, j2, direction, grid, false)
+// A snapshot of the warehouse after `step` moves, the last of which is `record`.
+pub(crate) struct State {
+    pub(crate) step: usize,
+    pub(crate) record: Option<MoveRecord>,
+    pub(crate) grid: String,
+}
+
+// Rows and columns are those of the file, counting from 0, before the map is scaled.
+#[derive(Debug, PartialEq)]
+pub(crate) enum WarehouseError {
+    ZeroScale,
+    MissingMoves,
+    UnevenRow {
+        row: usize,
+    },
+    OpenBorder {
+        row: usize,
+        column: usize,
+    },
+    NoRobot,
+    ExtraRobot {
+        row: usize,
+        column: usize,
+    },
+    UnexpectedTile {
+        row: usize,
+        column: usize,
+        tile: char,
+    },
+    MalformedBox {
+        row: usize,
+        column: usize,
+    },
+    BoxOverlapsWall {
+        row: usize,
+        column: usize,
+    },
+    InvalidMove {
+        row: usize,
+        column: usize,
+        found: char,
+    },
+}
+
+impl fmt::Display for WarehouseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarehouseError::ZeroScale => write!(f, "Tiles can't be scaled to no width"),
+            WarehouseError::MissingMoves => {
+                write!(f, "Missing blank line between the map and the moves")
             }
-        }
-        '[' => {
-            let (i2, j2) = direction.move_forward(grid, i, j).unwrap();
-            if *direction == Direction::Right || is_pair {
-                do_move('[', i2, j2, direction, grid, !is_pair)
-            } else {
-                do_move('.', i, j + 1, direction, grid, true);
-                do_move('[', i2, j2, direction, grid, false)
+            WarehouseError::UnevenRow { row } => {
+                write!(f, "Row {} is not as wide as the first one", row)
+            }
+            WarehouseError::OpenBorder { row, column } => {
+                write!(f, "Border is not a wall at row {}, column {}", row, column)
+            }
+            WarehouseError::NoRobot => write!(f, "No robot in the map"),
+            WarehouseError::ExtraRobot { row, column } => {
+                write!(f, "Second robot at row {}, column {}", row, column)
+            }
+            WarehouseError::UnexpectedTile { row, column, tile } => {
+                write!(
+                    f,
+                    "Unexpected tile {:?} at row {}, column {}",
+                    tile, row, column
+                )
+            }
+            WarehouseError::MalformedBox { row, column } => {
+                write!(f, "Malformed box at row {}, column {}", row, column)
+            }
+            WarehouseError::BoxOverlapsWall { row, column } => {
+                write!(f, "Box overlaps a wall at row {}, column {}", row, column)
+            }
+            WarehouseError::InvalidMove { row, column, found } => {
+                write!(
+                    f,
+                    "Invalid move {:?} at row {}, column {}",
+                    found, row, column
+                )
             }
         }
-        _ => (),
     }
-    grid[i][j] = prev
 }
 
-struct Robot {
+impl std::error::Error for WarehouseError {}
+
+pub(crate) struct Warehouse {
+    tiles: Vec<Vec<Tile>>,
+    crates: Vec<Crate>,
+    robot: Robot,
+    moves: Vec<Direction>,
+    // Records of the moves made so far, which are popped by `undo`. Unlike the log, which keeps
+    // every move ever made, its length is always the index of the next move.
+    history: Vec<MoveRecord>,
+    log: Option<Vec<MoveRecord>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Tile {
+    Empty,
+    Wall,
+    Robot,
+    Crate(usize),
+}
+
+// A box spanning `width` tiles to the right of its left side. Heavy boxes can't be pushed.
+struct Crate {
     i: usize,
     j: usize,
+    width: usize,
+    heavy: bool,
 }
 
-impl Robot {
-    fn new() -> Self {
-        Robot { i: 0, j: 0 }
-    }
-    fn try_move(&mut self, grid: &mut [Vec<char>], direction: &Direction) {
-        if let Some((i, j)) = direction.move_forward(grid, self.i, self.j) {
-            match grid[i][j] {
-                '.' => {
-                    grid[self.i][self.j] = '.';
-                    self.i = i;
-                    self.j = j;
-                    grid[i][j] = '@';
+impl Warehouse {
+    // Every tile but the robot is `scale` times as wide, the robot is followed by empty tiles.
+    // Boxes are `O`, or `[`, any number of `=` and `]` when wider. Heavy boxes use `X` and `{`, `}`.
+    pub(crate) fn parse(contents: &str, scale: usize) -> Result<Self, WarehouseError> {
+        if scale == 0 {
+            return Err(WarehouseError::ZeroScale);
+        }
+        // `lines` also strips the `\r` of windows line endings.
+        let lines = contents.lines().collect_vec();
+        let blank = lines
+            .iter()
+            .position(|line| line.is_empty())
+            .ok_or(WarehouseError::MissingMoves)?;
+        let (map, moves) = (&lines[..blank], &lines[blank + 1..]);
+        let width = map.first().map_or(0, |line| line.chars().count());
+        let mut robot = None;
+        let mut crates: Vec<Crate> = vec![];
+        let mut tiles = Vec::with_capacity(map.len());
+        for (i, line) in map.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err(WarehouseError::UnevenRow { row: i });
+            }
+            let mut row = Vec::with_capacity(width * scale);
+            // Id and column of the wide box being read, if any.
+            let mut open_crate: Option<(usize, usize)> = None;
+            for (column, position) in line.chars().enumerate() {
+                let j = row.len();
+                let border = i == 0 || i + 1 == map.len() || column == 0 || column + 1 == width;
+                if border && position != '#' {
+                    return Err(WarehouseError::OpenBorder { row: i, column });
                 }
-                'O' => {
-                    let (mut i_n, mut j_n) = (i, j);
-                    while let Some((i_n1, j_n1)) = direction.move_forward(grid, i_n, j_n) {
-                        i_n = i_n1;
-                        j_n = j_n1;
-                        match grid[i_n][j_n] {
-                            '.' => {
-                                grid[self.i][self.j] = '.';
-                                self.i = i;
-                                self.j = j;
-                                grid[i][j] = '@';
-                                grid[i_n][j_n] = 'O';
-                                return;
-                            }
-                            'O' => continue,
-                            _ => return,
+                match (position, open_crate) {
+                    ('=' | ']' | '}', Some((id, _))) => {
+                        if position != '=' && (position == '}') != crates[id].heavy {
+                            return Err(WarehouseError::MalformedBox { row: i, column });
+                        }
+                        crates[id].width += scale;
+                        row.extend(std::iter::repeat_n(Tile::Crate(id), scale));
+                        if position != '=' {
+                            open_crate = None;
+                        }
+                        continue;
+                    }
+                    ('#', Some(_)) => {
+                        return Err(WarehouseError::BoxOverlapsWall { row: i, column });
+                    }
+                    (_, Some((_, start))) => {
+                        return Err(WarehouseError::MalformedBox {
+                            row: i,
+                            column: start,
+                        });
+                    }
+                    _ => (),
+                }
+                match position {
+                    '@' if robot.is_some() => {
+                        return Err(WarehouseError::ExtraRobot { row: i, column });
+                    }
+                    '@' => {
+                        robot = Some(Robot { i, j });
+                        row.push(Tile::Robot);
+                        row.extend(std::iter::repeat_n(Tile::Empty, scale - 1));
+                    }
+                    '#' => row.extend(std::iter::repeat_n(Tile::Wall, scale)),
+                    '.' => row.extend(std::iter::repeat_n(Tile::Empty, scale)),
+                    'O' | 'X' | '[' | '{' => {
+                        let heavy = matches!(position, 'X' | '{');
+                        crates.push(Crate {
+                            i,
+                            j,
+                            width: scale,
+                            heavy,
+                        });
+                        row.extend(std::iter::repeat_n(Tile::Crate(crates.len() - 1), scale));
+                        if matches!(position, '[' | '{') {
+                            open_crate = Some((crates.len() - 1, column));
                         }
                     }
+                    '=' | ']' | '}' => {
+                        return Err(WarehouseError::MalformedBox { row: i, column });
+                    }
+                    tile => {
+                        return Err(WarehouseError::UnexpectedTile {
+                            row: i,
+                            column,
+                            tile,
+                        });
+                    }
                 }
-                _ => (),
             }
+            tiles.push(row);
+        }
+        let robot = robot.ok_or(WarehouseError::NoRobot)?;
+        let moves = moves
+            .iter()
+            .enumerate()
+            .flat_map(|(k, line)| {
+                line.chars()
+                    .enumerate()
+                    .map(move |(column, c)| (blank + 1 + k, column, c))
+            })
+            .map(|(row, column, direction)| {
+                Direction::try_from_option(direction).ok_or(WarehouseError::InvalidMove {
+                    row,
+                    column,
+                    found: direction,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Warehouse {
+            tiles,
+            crates,
+            robot,
+            moves,
+            history: vec![],
+            log: None,
+        })
+    }
+
+    pub(crate) fn with_log(mut self) -> Self {
+        self.log = Some(vec![]);
+        self
+    }
+
+    pub(crate) fn log(&self) -> Option<&[MoveRecord]> {
+        self.log.as_deref()
+    }
+
+    // Makes the next move, if any is left, and returns what happened.
+    pub(crate) fn step(&mut self) -> Option<MoveRecord> {
+        let direction = *self.moves.get(self.history.len())?;
+        let pushed = self.pushed_crates(direction);
+        if let Some(pushed) = &pushed {
+            let (i, j) = (self.robot.i, self.robot.j);
+            self.shift_crates(pushed, direction);
+            self.tiles[i][j] = Tile::Empty;
+            (self.robot.i, self.robot.j) = direction.move_forward(&self.tiles, i, j).unwrap();
+            self.tiles[self.robot.i][self.robot.j] = Tile::Robot;
+        }
+        let record = MoveRecord {
+            direction,
+            moved: pushed.is_some(),
+            boxes: pushed
+                .unwrap_or_default()
+                .iter()
+                .map(|&id| (self.crates[id].i, self.crates[id].j))
+                .map(|(i, j)| {
+                    direction
+                        .opposite()
+                        .move_forward(&self.tiles, i, j)
+                        .unwrap()
+                })
+                .collect(),
+        };
+        if let Some(log) = &mut self.log {
+            log.push(record.clone());
+        }
+        self.history.push(record.clone());
+        Some(record)
+    }
+
+    // Reverts the last move made and returns its record. The robot steps back first, since the
+    // first box pushed goes back to where the robot is now.
+    pub(crate) fn undo(&mut self) -> Option<MoveRecord> {
+        let record = self.history.pop()?;
+        if record.moved {
+            let (i, j) = (self.robot.i, self.robot.j);
+            let back = record.direction.opposite();
+            self.tiles[i][j] = Tile::Empty;
+            (self.robot.i, self.robot.j) = back.move_forward(&self.tiles, i, j).unwrap();
+            self.tiles[self.robot.i][self.robot.j] = Tile::Robot;
+            let pushed = record
+                .boxes
+                .iter()
+                .filter_map(|&(i, j)| {
+                    let (i, j) = record.direction.move_forward(&self.tiles, i, j)?;
+                    match self.tiles[i][j] {
+                        Tile::Crate(id) => Some(id),
+                        _ => None,
+                    }
+                })
+                .collect_vec();
+            self.shift_crates(&pushed, back);
         }
+        Some(record)
     }
 
-    fn try_move_wide(&mut self, grid: &mut [Vec<char>], direction: &Direction) {
-        if let Some((i, j)) = direction.move_forward(grid, self.i, self.j) {
-            if can_move(i, j, direction, grid, false) {
-                grid[self.i][self.j] = '.';
-                self.i = i;
-                self.j = j;
-                do_move('@', i, j, direction, grid, false);
+    // The boxes the robot would push, nearest first, or None if it can't move. Every box found
+    // checks the tiles ahead of its whole width, so boxes of any size can be mixed.
+    fn pushed_crates(&self, direction: Direction) -> Option<Vec<usize>> {
+        let mut ahead =
+            VecDeque::from([direction.move_forward(&self.tiles, self.robot.i, self.robot.j)?]);
+        let mut pushed: Vec<usize> = vec![];
+        while let Some((i, j)) = ahead.pop_front() {
+            match self.tiles[i][j] {
+                Tile::Empty => (),
+                Tile::Crate(id) if !pushed.contains(&id) && !self.crates[id].heavy => {
+                    pushed.push(id);
+                    let Crate { i, j, width, .. } = self.crates[id];
+                    for j in j..j + width {
+                        let (i, j) = direction.move_forward(&self.tiles, i, j)?;
+                        if self.tiles[i][j] != Tile::Crate(id) {
+                            ahead.push_back((i, j));
+                        }
+                    }
+                }
+                Tile::Crate(id) if pushed.contains(&id) => (),
+                _ => return None,
             }
         }
+        Some(pushed)
+    }
+
+    fn shift_crates(&mut self, ids: &[usize], direction: Direction) {
+        ids.iter().for_each(|&id| {
+            let Crate { i, j, width, .. } = self.crates[id];
+            self.tiles[i][j..j + width].fill(Tile::Empty);
+        });
+        ids.iter().for_each(|&id| {
+            let Crate { i, j, width, .. } = self.crates[id];
+            let (i, j) = direction.move_forward(&self.tiles, i, j).unwrap();
+            (self.crates[id].i, self.crates[id].j) = (i, j);
+            self.tiles[i][j..j + width].fill(Tile::Crate(id));
+        });
+    }
+
+    // Undoes or makes moves until `step` moves have been made.
+    pub(crate) fn rewind_to(&mut self, step: usize) {
+        while self.history.len() > step && self.undo().is_some() {}
+        while self.history.len() < step && self.step().is_some() {}
+    }
+
+    // The states from move `step` onwards, replayed on the same warehouse.
+    pub(crate) fn replay(&mut self, step: usize) -> impl Iterator<Item = State> + '_ {
+        self.rewind_to(step);
+        self.states()
+    }
+
+    pub(crate) fn run(&mut self) {
+        while self.step().is_some() {}
+    }
+
+    // The current state followed by the state after each remaining move.
+    pub(crate) fn states(&mut self) -> impl Iterator<Item = State> + '_ {
+        std::iter::once(self.state()).chain(std::iter::from_fn(move || {
+            self.step()?;
+            Some(self.state())
+        }))
+    }
+
+    pub(crate) fn state(&self) -> State {
+        State {
+            step: self.history.len(),
+            record: self.history.last().cloned(),
+            grid: self.render(),
+        }
+    }
+
+    pub(crate) fn gps_sum(&self) -> usize {
+        self.crates
+            .iter()
+            .map(|Crate { i, j, .. }| 100 * i + j)
+            .sum()
+    }
+
+    pub(crate) fn render(&self) -> String {
+        self.tiles
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(j, tile)| match tile {
+                        Tile::Empty => '.',
+                        Tile::Wall => '#',
+                        Tile::Robot => '@',
+                        Tile::Crate(id) => {
+                            let Crate {
+                                j: start,
+                                width,
+                                heavy,
+                                ..
+                            } = self.crates[*id];
+                            match (width, j - start, heavy) {
+                                (1, _, false) => 'O',
+                                (1, _, true) => 'X',
+                                (_, 0, false) => '[',
+                                (_, 0, true) => '{',
+                                (_, k, false) if k + 1 == width => ']',
+                                (_, k, true) if k + 1 == width => '}',
+                                _ => '=',
+                            }
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
     }
 }
 
-#[derive(PartialEq)]
-enum Direction {
+struct Robot {
+    i: usize,
+    j: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Direction {
     Right,
     Up,
     Down,
     Left,
 }
 
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self {
+            Direction::Right => '>',
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+        };
+        write!(f, "{}", arrow)
+    }
+}
+
 impl Direction {
+    fn opposite(&self) -> Self {
+        match self {
+            Direction::Right => Direction::Left,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+        }
+    }
+
     fn move_forward<T>(&self, grid: &[Vec<T>], i: usize, j: usize) -> Option<(usize, usize)> {
         let height = grid.len();
         let width = grid[0].len();
@@ -248,3 +516,221 @@ impl Direction {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+##########
+#..O..O.O#
+#......O.#
+#.OO..O.O#
+#..O@..O.#
+#O#..O...#
+#O..O..O.#
+#.OO.O.OO#
+#....O...#
+##########
+
+<vv>^<v^>v>^vv^v>v<>v^v<v<^vv<<<^><<><>>v<vvv<>^v^>^<<<><<v<<<v^vv^v>^
+vvv<<^>^v^^><<>>><>^<<><^vv^^<>vvv<>><^^v>^>vv<>v<<<<v<^v>^<^^>>>^<v<v
+><>vv>v^v^<>><>>>><^^>vv>v<^^^>>v^v^<^^>v^^>v^<^v>v<>>v^v^<v>v^^<^^vv<
+<<v<^>>^^^^>>>v^<>vvv^><v<<<>^^^vv^<vvv>^>v<^^^^v<>^>vvvv><>>v^<<^^^^^
+^><^><>>><>^^<<^^v>>><^<v>^<vv>>v>>>^v><>^v><<<<v>>v<v<v>vvv>^<><<>^><
+^>><>^v<><^vvv<^^<><v<<<<<><^v<<<><<<^^<v<^^^><^>>^<v^><<<^>>^v<v^v<v^
+>^>>^v>vv>^<<^v<>><<><<v<<v><>v<^vv<<<>^^v^>^^>>><<^v>>v^v><^^>>^<>vv^
+<><^^>^^^<><vvvvv^v<v<<>^v<v>v<<^><<><<><<<^^<<<^<<>><<><^^^>^^<>^>v<>
+^^>vv<^v^v<vv>^<><v<^v>^^^>>>^^vvv^>vvv<>>>^<^>>>>>^<<^v>^vvv<>^<><<v>
+v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^";
+
+    const SMALL_EXAMPLE: &str = "\
+########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########
+
+<^^>>>vv<v>>v<<";
+
+    #[test]
+    fn test_example() -> anyhow::Result<()> {
+        let mut warehouse = Warehouse::parse(EXAMPLE, 1)?;
+        warehouse.run();
+        assert_eq!(warehouse.gps_sum(), 10092);
+        let mut warehouse = Warehouse::parse(EXAMPLE, 2)?;
+        warehouse.run();
+        assert_eq!(warehouse.gps_sum(), 9021);
+        Ok(())
+    }
+
+    #[test]
+    fn test_states_and_log() -> anyhow::Result<()> {
+        let mut warehouse = Warehouse::parse(SMALL_EXAMPLE, 1)?.with_log();
+        let states: Vec<State> = warehouse.states().collect();
+        assert_eq!(states.len(), 16);
+        assert_eq!(states[0].step, 0);
+        assert!(states[0].record.is_none());
+        let expected = "\
+########
+#.@O.O.#
+##..O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########";
+        assert_eq!(states[2].grid, expected);
+        let log = warehouse.log().unwrap();
+        assert_eq!(log.len(), 15);
+        assert_eq!(log[0].to_string(), "< blocked");
+        assert_eq!(log[3].to_string(), "> moved, pushed (1,3)");
+        assert_eq!(log[4].to_string(), "> moved, pushed (1,4) (1,5)");
+        assert_eq!(log[5].to_string(), "> blocked");
+        assert_eq!(warehouse.gps_sum(), 2028);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wide_log() -> anyhow::Result<()> {
+        let contents =
+            "#######\n#...#.#\n#.....#\n#..OO@#\n#..O..#\n#.....#\n#######\n\n<vv<<^^<<^^";
+        let mut warehouse = Warehouse::parse(contents, 2)?.with_log();
+        warehouse.run();
+        let log = warehouse.log().unwrap();
+        assert_eq!(log[0].boxes, vec![(3, 8), (3, 6)]);
+        assert_eq!(log[5].to_string(), "^ moved, pushed (4,6) (3,5) (3,7)");
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_and_replay() -> anyhow::Result<()> {
+        for scale in [1, 2, 3] {
+            let mut warehouse = Warehouse::parse(EXAMPLE, scale)?;
+            let grids: Vec<String> = warehouse.states().map(|state| state.grid).collect();
+            assert_eq!(grids.len(), 701);
+            (0..700).rev().for_each(|step| {
+                warehouse.undo();
+                assert_eq!(warehouse.render(), grids[step], "undoing move {}", step);
+            });
+            assert!(warehouse.undo().is_none());
+            let replayed: Vec<String> = warehouse.replay(350).map(|state| state.grid).collect();
+            assert_eq!(replayed, grids[350..]);
+            warehouse.rewind_to(123);
+            assert_eq!(warehouse.render(), grids[123]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_boxes() -> anyhow::Result<()> {
+        let contents = "########\n#......#\n#.[=]..#\n#..O.X.#\n#..@...#\n########\n\n^^>>^<";
+        let mut warehouse = Warehouse::parse(contents, 1)?.with_log();
+        let initial = warehouse.render();
+        warehouse.run();
+        let log = warehouse
+            .log()
+            .unwrap()
+            .iter()
+            .map(|record| record.to_string())
+            .collect_vec();
+        assert_eq!(
+            log,
+            vec![
+                "^ moved, pushed (3,3) (2,2)",
+                "^ blocked",
+                "> moved",
+                "> blocked",
+                "^ moved",
+                "< moved, pushed (2,3)"
+            ]
+        );
+        let expected = "\
+########
+#.[=]..#
+#.O@...#
+#....X.#
+#......#
+########";
+        assert_eq!(warehouse.render(), expected);
+        assert_eq!(warehouse.gps_sum(), 102 + 202 + 305);
+        warehouse.rewind_to(0);
+        assert_eq!(warehouse.render(), initial);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validation() {
+        let error = |contents: &str| Warehouse::parse(contents, 1).err();
+        assert_eq!(
+            error("#####\n#.@.#\n#####\n"),
+            Some(WarehouseError::MissingMoves)
+        );
+        assert_eq!(
+            error("#####\n#...#\n#####\n\n<"),
+            Some(WarehouseError::NoRobot)
+        );
+        assert_eq!(
+            error("#####\n#@.@#\n#####\n\n<"),
+            Some(WarehouseError::ExtraRobot { row: 1, column: 3 })
+        );
+        assert_eq!(
+            error("#####\n#.@..\n#####\n\n<"),
+            Some(WarehouseError::OpenBorder { row: 1, column: 4 })
+        );
+        assert_eq!(
+            error("#####\n#.@.#\n####\n\n<"),
+            Some(WarehouseError::UnevenRow { row: 2 })
+        );
+        assert_eq!(
+            error("#####\n#.@*#\n#####\n\n<"),
+            Some(WarehouseError::UnexpectedTile {
+                row: 1,
+                column: 3,
+                tile: '*'
+            })
+        );
+        assert_eq!(
+            error("######\n#@[#]#\n######\n\n<"),
+            Some(WarehouseError::BoxOverlapsWall { row: 1, column: 3 })
+        );
+        assert_eq!(
+            error("######\n#@[.]#\n######\n\n<"),
+            Some(WarehouseError::MalformedBox { row: 1, column: 2 })
+        );
+        assert_eq!(
+            error("######\n#@[}.#\n######\n\n<"),
+            Some(WarehouseError::MalformedBox { row: 1, column: 3 })
+        );
+        assert_eq!(
+            error("#####\n#.@.#\n#####\n\n<>\n^v\r^\n"),
+            Some(WarehouseError::InvalidMove {
+                row: 5,
+                column: 2,
+                found: '\r'
+            })
+        );
+        assert_eq!(
+            Warehouse::parse("#####\n#.@.#\n#####\n\n<>\n^v\n", 0).err(),
+            Some(WarehouseError::ZeroScale)
+        );
+    }
+
+    #[test]
+    fn test_windows_line_endings() -> anyhow::Result<()> {
+        let mut warehouse = Warehouse::parse(&SMALL_EXAMPLE.replace('\n', "\r\n"), 1)?;
+        warehouse.run();
+        assert_eq!(warehouse.gps_sum(), 2028);
+        Ok(())
+    }
+
+    #[test]
+    fn test_input() -> anyhow::Result<()> {
+        assert_eq!(sum_box_positions()?, 1437174);
+        assert_eq!(sum_wide_box_positions()?, 1437468);
+        Ok(())
+    }
+}
@@ -1,89 +1,339 @@
-use std::fs::read;
+use std::{fmt, fs::read_to_string, str::FromStr};
+
+use anyhow::{bail, Context};
+use itertools::Itertools;
 
 const PATH: &str = "src/dec2/input.txt";
 
-enum Direction {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Direction {
     Increasing,
     Decreasing,
 }
 
-pub(crate) fn safe_count() -> usize {
-    let bytes = read(PATH).unwrap();
-    let reports = String::from_utf8(bytes).unwrap();
-    reports.lines().filter(|&report| is_safe(report.split_ascii_whitespace(), true)).count()
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Increasing => write!(f, "increasing"),
+            Direction::Decreasing => write!(f, "decreasing"),
+        }
+    }
 }
 
-pub(crate) fn dampened_count() -> usize {
-    let bytes = read(PATH).unwrap();
-    let reports = String::from_utf8(bytes).unwrap();
-    reports.lines().filter(|&report| is_safe(report.split_ascii_whitespace(), false)
-            || is_safe(report.split_ascii_whitespace().rev(), false)
-    ).count()
+// A report is safe if removing at most `max_removed` levels leaves every step between adjacent
+// levels within `min_step..=max_step`, all in the same direction, which can be required.
+pub(crate) struct ReportPolicy {
+    pub(crate) min_step: i32,
+    pub(crate) max_step: i32,
+    pub(crate) max_removed: usize,
+    pub(crate) direction: Option<Direction>,
 }
 
-fn is_safe<'a, I: Iterator<Item = &'a str>>(report: I, mut already_dampened: bool) -> bool {
-    let mut prev_opt = None;
-    let mut dir_opt = None;
-    for word in report {
-        let level = word.parse::<i32>().unwrap();
-        if let Some(prev) = prev_opt {
-            match dir_opt {
-                Some(Direction::Decreasing) => match prev - level {
-                    1..=3 => (),
-                    _ => {
-                        if already_dampened {
-                            return false;
-                        } else {
-                            already_dampened = true;
-                            continue;
-                        }
-                    }
-                },
-                Some(Direction::Increasing) => match level - prev {
-                    1..=3 => (),
-                    _ => {
-                        if already_dampened {
-                            return false;
-                        } else {
-                            already_dampened = true;
-                            continue;
-                        }
-                    }
-                },
-                None => match level - prev {
-                    -3..=-1 => {
-                        dir_opt = Some(Direction::Decreasing);
-                    }
-                    1..=3 => {
-                        dir_opt = Some(Direction::Increasing);
-                    }
-                    _ => {
-                        if already_dampened {
-                            return false;
-                        } else {
-                            already_dampened = true;
-                            continue;
+pub(crate) const STRICT: ReportPolicy = ReportPolicy {
+    min_step: 1,
+    max_step: 3,
+    max_removed: 0,
+    direction: None,
+};
+
+pub(crate) const DAMPENED: ReportPolicy = ReportPolicy {
+    max_removed: 1,
+    ..STRICT
+};
+
+pub(crate) fn safe_count() -> anyhow::Result<usize> {
+    count_safe(&STRICT)
+}
+
+pub(crate) fn dampened_count() -> anyhow::Result<usize> {
+    count_safe(&DAMPENED)
+}
+
+pub(crate) fn count_safe(policy: &ReportPolicy) -> anyhow::Result<usize> {
+    let reports = read_reports()?;
+    Ok(reports.iter().filter(|report| policy.is_safe(report)).count())
+}
+
+fn read_reports() -> anyhow::Result<Vec<Vec<i32>>> {
+    read_to_string(PATH)?
+        .lines()
+        .enumerate()
+        .map(|(i, report)| {
+            report
+                .split_ascii_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .with_context(|| format!("Invalid report on line {}", i + 1))
+        })
+        .collect()
+}
+
+impl ReportPolicy {
+    pub(crate) fn is_safe(&self, levels: &[i32]) -> bool {
+        self.min_removals(levels) <= self.max_removed
+    }
+
+    pub(crate) fn min_removals(&self, levels: &[i32]) -> usize {
+        let (_, chain) = self.longest_chain(levels);
+        levels.len() - chain.len()
+    }
+
+    // The fewest levels to remove is what is left out of the longest chain of levels, in order,
+    // where every step is allowed. `longest[j]` is the longest such chain ending at level j, and
+    // `previous[j]` the level before j in it. Ties keep later levels and increasing reports, so
+    // `8 6 4 4 1` drops its first 4, like in the Readme.
+    fn longest_chain(&self, levels: &[i32]) -> (Direction, Vec<usize>) {
+        let directions = match self.direction {
+            Some(direction) => vec![direction],
+            None => vec![Direction::Increasing, Direction::Decreasing],
+        };
+        directions
+            .into_iter()
+            .map(|direction| {
+                let mut longest = vec![1; levels.len()];
+                let mut previous = vec![None; levels.len()];
+                for j in 0..levels.len() {
+                    for i in 0..j {
+                        if self.allows(direction, levels[i], levels[j])
+                            && longest[i] + 1 >= longest[j]
+                        {
+                            longest[j] = longest[i] + 1;
+                            previous[j] = Some(i);
                         }
                     }
-                },
-            }
+                }
+                let mut chain = vec![];
+                let mut last = longest.iter().position_max();
+                while let Some(j) = last {
+                    chain.push(j);
+                    last = previous[j];
+                }
+                chain.reverse();
+                (direction, chain)
+            })
+            .rev()
+            .max_by_key(|(_, chain)| chain.len())
+            .unwrap_or((Direction::Increasing, vec![]))
+    }
+
+    pub(crate) fn explain(&self, line: usize, levels: &[i32]) -> ReportExplanation {
+        let (direction, chain) = self.longest_chain(levels);
+        let safe = levels.len() - chain.len() <= self.max_removed;
+        let first_offence = (1..levels.len())
+            .find(|&i| !self.allows(direction, levels[i - 1], levels[i]))
+            .map(|i| (i, levels[i] - levels[i - 1]));
+        let removed = if safe {
+            (0..levels.len()).filter(|i| !chain.contains(i)).collect()
+        } else {
+            vec![]
+        };
+        ReportExplanation {
+            line,
+            levels: levels.to_vec(),
+            safe,
+            direction,
+            first_offence,
+            removed,
+        }
+    }
+
+    fn allows(&self, direction: Direction, prev: i32, level: i32) -> bool {
+        let step = match direction {
+            Direction::Increasing => level - prev,
+            Direction::Decreasing => prev - level,
+        };
+        (self.min_step..=self.max_step).contains(&step)
+    }
+}
+
+// Indices count from 0 and lines from 1. The direction is the one of the kept levels, and the
+// first offence is the first step of the whole report that breaks the policy in it, if any. The
+// removed levels are only given for safe reports.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ReportExplanation {
+    pub(crate) line: usize,
+    pub(crate) levels: Vec<i32>,
+    pub(crate) safe: bool,
+    pub(crate) direction: Direction,
+    pub(crate) first_offence: Option<(usize, i32)>,
+    pub(crate) removed: Vec<usize>,
+}
+
+pub(crate) fn explain_reports(policy: &ReportPolicy) -> anyhow::Result<Vec<ReportExplanation>> {
+    let reports = read_reports()?;
+    Ok(reports
+        .iter()
+        .enumerate()
+        .map(|(i, report)| policy.explain(i + 1, report))
+        .collect())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => bail!("Unknown export format {}", s),
+        }
+    }
+}
+
+// Lists are space separated in CSV, and missing offences are empty cells in CSV and nulls in JSON.
+pub(crate) fn export_explanations(
+    explanations: &[ReportExplanation],
+    format: ExportFormat,
+) -> String {
+    match format {
+        ExportFormat::Csv => {
+            let header =
+                "line,levels,safe,direction,offending_index,difference,removed".to_string();
+            let rows = explanations.iter().map(|e| {
+                let (index, difference) = e.first_offence.unzip();
+                format!(
+                    "{},{},{},{},{},{},{}",
+                    e.line,
+                    e.levels.iter().join(" "),
+                    e.safe,
+                    e.direction,
+                    index.map_or(String::new(), |index| index.to_string()),
+                    difference.map_or(String::new(), |difference| difference.to_string()),
+                    e.removed.iter().join(" ")
+                )
+            });
+            std::iter::once(header).chain(rows).join("\n")
+        }
+        ExportFormat::Json => {
+            let objects = explanations.iter().map(|e| {
+                let (index, difference) = e.first_offence.unzip();
+                format!(
+                    "  {{\"line\": {}, \"levels\": [{}], \"safe\": {}, \"direction\": \"{}\", \"offending_index\": {}, \"difference\": {}, \"removed\": [{}]}}",
+                    e.line,
+                    e.levels.iter().join(", "),
+                    e.safe,
+                    e.direction,
+                    index.map_or("null".to_string(), |index| index.to_string()),
+                    difference.map_or("null".to_string(), |difference| difference.to_string()),
+                    e.removed.iter().join(", ")
+                )
+            });
+            format!("[\n{}\n]", objects.format(",\n"))
         }
-        prev_opt = Some(level);
     }
-    true
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    const EXAMPLE: [&[i32]; 6] = [
+        &[7, 6, 4, 2, 1],
+        &[1, 2, 7, 8, 9],
+        &[9, 7, 6, 2, 1],
+        &[1, 3, 2, 4, 5],
+        &[8, 6, 4, 4, 1],
+        &[1, 3, 6, 7, 9],
+    ];
+
+    #[test]
+    fn test_safe_count() -> anyhow::Result<()> {
+        assert_eq!(safe_count()?, 332);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dampened_count() -> anyhow::Result<()> {
+        assert_eq!(dampened_count()?, 398);
+        Ok(())
+    }
+
     #[test]
-    fn test_safe_count() {
-        assert_eq!(safe_count(), 332);
+    fn test_policies() {
+        let count = |policy: &ReportPolicy| EXAMPLE.iter().filter(|r| policy.is_safe(r)).count();
+        assert_eq!(count(&STRICT), 2);
+        assert_eq!(count(&DAMPENED), 4);
+        let decreasing = ReportPolicy {
+            direction: Some(Direction::Decreasing),
+            ..DAMPENED
+        };
+        assert_eq!(count(&decreasing), 2);
+        let removals = EXAMPLE.iter().map(|r| STRICT.min_removals(r)).collect_vec();
+        assert_eq!(removals, vec![0, 2, 2, 1, 1, 0]);
     }
 
     #[test]
-    fn test_dampened_count() {
-        assert_eq!(dampened_count(), 398);
+    fn test_explanations() {
+        let explanations = EXAMPLE
+            .iter()
+            .enumerate()
+            .map(|(i, report)| DAMPENED.explain(i + 1, report))
+            .collect_vec();
+        let summary = explanations
+            .iter()
+            .map(|e| (e.safe, e.direction, e.first_offence, e.removed.clone()))
+            .collect_vec();
+        assert_eq!(
+            summary,
+            vec![
+                (true, Direction::Decreasing, None, vec![]),
+                (false, Direction::Increasing, Some((2, 5)), vec![]),
+                (false, Direction::Decreasing, Some((3, -4)), vec![]),
+                (true, Direction::Increasing, Some((2, -1)), vec![1]),
+                (true, Direction::Decreasing, Some((3, 0)), vec![2]),
+                (true, Direction::Increasing, None, vec![]),
+            ]
+        );
+        let csv = export_explanations(&explanations[3..4], ExportFormat::Csv);
+        assert_eq!(
+            csv,
+            "line,levels,safe,direction,offending_index,difference,removed\n\
+             4,1 3 2 4 5,true,increasing,2,-1,1"
+        );
+        let json = export_explanations(&explanations[..1], ExportFormat::Json);
+        assert_eq!(
+            json,
+            "[\n  {\"line\": 1, \"levels\": [7, 6, 4, 2, 1], \"safe\": true, \"direction\": \"decreasing\", \
+             \"offending_index\": null, \"difference\": null, \"removed\": []}\n]"
+        );
     }
-}
\ No newline at end of file
+
+    // Tries every way of removing up to `max_removed` levels.
+    fn brute_force(policy: &ReportPolicy, levels: &[i32]) -> bool {
+        (0..=policy.max_removed.min(levels.len())).any(|removed| {
+            (0..levels.len()).combinations(removed).any(|removed| {
+                let kept = (0..levels.len())
+                    .filter(|i| !removed.contains(i))
+                    .map(|i| levels[i])
+                    .collect_vec();
+                [Direction::Increasing, Direction::Decreasing]
+                    .into_iter()
+                    .filter(|&direction| policy.direction.is_none_or(|d| d == direction))
+                    .any(|direction| {
+                        kept.windows(2).all(|pair| policy.allows(direction, pair[0], pair[1]))
+                    })
+            })
+        })
+    }
+
+    #[test]
+    fn test_any_tolerance() -> anyhow::Result<()> {
+        let reports = read_reports()?;
+        for max_removed in 0..=3 {
+            let policy = ReportPolicy {
+                max_removed,
+                ..STRICT
+            };
+            reports.iter().for_each(|report| {
+                assert_eq!(policy.is_safe(report), brute_force(&policy, report), "{:?}", report)
+            });
+        }
+        Ok(())
+    }
+}
@@ -1,13 +1,18 @@
-use anyhow::Context;
+use anyhow::{bail, Context};
 use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use std::{
     cmp::Ordering,
     collections::{HashMap, HashSet},
+    fmt,
     fs::read_to_string,
+    str::FromStr,
 };
 
 const PATH: &str = "src/dec5/input.txt";
 
+type Rule = [usize; 2];
+
 #[derive(Default)]
 struct Order {
     predecessors: HashSet<usize>,
@@ -15,23 +20,15 @@ struct Order {
 }
 
 pub(crate) fn middle_page_ordered_updates_sum() -> anyhow::Result<usize> {
-    let content = read_to_string(PATH)?;
-    let mut split_content = content.split("\n\n");
-    let (raw_rules, updates) = (
-        split_content.next().context("No rules")?,
-        split_content.next().context("No updates")?,
-    );
-    let rules = create_rules(raw_rules);
-
-    let result = updates
-        .lines()
-        .map(|l| {
-            l.split(",")
-                .map(|w| w.parse::<usize>().unwrap_or_default())
-                .collect::<Vec<usize>>()
-        })
+    let (rules, updates) = read_rules_and_updates()?;
+    Ok(brute_force_sum(&rules, &updates))
+}
+
+fn brute_force_sum(rules: &[[usize; 2]], updates: &[Vec<usize>]) -> usize {
+    updates
+        .iter()
         .map(|u| {
-            let ordered = rules.clone().all(|[first, second]| {
+            let ordered = rules.iter().all(|&[first, second]| {
                 if let Some(first_pos) = u.iter().position(|&x| x == first) {
                     if let Some(second_pos) = u.iter().position(|&x| x == second) {
                         return first_pos < second_pos;
@@ -45,25 +42,21 @@ pub(crate) fn middle_page_ordered_updates_sum() -> anyhow::Result<usize> {
             }
             0
         })
-        .sum();
-
-    Ok(result)
+        .sum()
 }
 
 pub(crate) fn middle_page_sum(ordered: bool) -> anyhow::Result<usize> {
-    let content = read_to_string(PATH)?;
-    let mut split_content = content.split("\n\n");
-    let (raw_rules, raw_updates) = (
-        split_content.next().context("No rules")?,
-        split_content.next().context("No updates")?,
-    );
+    let (rules, updates) = read_rules_and_updates()?;
+    Ok(predecessors_sum(&rules, &updates, ordered))
+}
 
-    let rules = traverse_rules(create_rules(raw_rules));
-    let updates = create_updates(raw_updates);
-    let result = updates
+fn predecessors_sum(rules: &[[usize; 2]], updates: &[Vec<usize>], ordered: bool) -> usize {
+    let rules = traverse_rules(rules.iter().copied());
+    updates
+        .iter()
         .map(|update| {
-            let ordered_update = create_order(filter_updates(&rules, &update));
-            let is_ordered = ordered_update == update;
+            let ordered_update = create_order(filter_updates(&rules, update));
+            let is_ordered = ordered_update == *update;
             if is_ordered == ordered {
                 let mid = ordered_update.len() / 2;
                 ordered_update[mid]
@@ -71,8 +64,20 @@ pub(crate) fn middle_page_sum(ordered: bool) -> anyhow::Result<usize> {
                 0
             }
         })
-        .sum();
-    Ok(result)
+        .sum()
+}
+
+fn read_rules_and_updates() -> anyhow::Result<(Vec<Rule>, Vec<Vec<usize>>)> {
+    let content = read_to_string(PATH)?;
+    let mut split_content = content.split("\n\n");
+    let (raw_rules, raw_updates) = (
+        split_content.next().context("No rules")?,
+        split_content.next().context("No updates")?,
+    );
+    Ok((
+        create_rules(raw_rules).collect(),
+        create_updates(raw_updates).collect(),
+    ))
 }
 
 fn create_rules<'a>(raw_rules: &'a str) -> impl Iterator<Item = [usize; 2]> + 'a + Clone {
@@ -144,15 +149,14 @@ fn hash_rules<I: Iterator<Item = [usize; 2]>>(rules: I) -> HashMap<usize, HashSe
 }
 
 pub(crate) fn page_custom_order(ordered: bool) -> anyhow::Result<usize> {
-    let content = read_to_string(PATH)?;
-    let mut split_content = content.split("\n\n");
-    let (raw_rules, raw_updates) = (
-        split_content.next().context("No rules")?,
-        split_content.next().context("No updates")?,
-    );
-    let rules = hash_rules(create_rules(raw_rules));
-    let updates = create_updates(raw_updates);
-    let result = updates
+    let (rules, updates) = read_rules_and_updates()?;
+    Ok(custom_order_sum(&rules, &updates, ordered))
+}
+
+fn custom_order_sum(rules: &[[usize; 2]], updates: &[Vec<usize>], ordered: bool) -> usize {
+    let rules = hash_rules(rules.iter().copied());
+    updates
+        .iter()
         .map(|update| {
             let sorted_update: Vec<usize> = update.iter().sorted_unstable_by(|this, other| {
                 if let Some(self_entry) = rules.get(this) {
@@ -168,16 +172,419 @@ pub(crate) fn page_custom_order(ordered: bool) -> anyhow::Result<usize> {
                 }
                 Ordering::Equal
             }).copied().collect();
-            let is_ordered = sorted_update == update;
+            let is_ordered = sorted_update == *update;
             if is_ordered == ordered {
               let mid = sorted_update.len() / 2;
               return sorted_update[mid];
             }
             0
         })
-        .sum();
+        .sum()
+}
 
-    Ok(result)
+#[derive(Debug, PartialEq)]
+pub(crate) enum OrderError {
+    Cycle(Vec<usize>),
+    Ambiguous(usize, usize),
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderError::Cycle(pages) => write!(
+                f,
+                "Rules form a cycle {} -> {}",
+                pages.iter().join(" -> "),
+                pages[0]
+            ),
+            OrderError::Ambiguous(first, second) => {
+                write!(f, "No rule orders pages {} and {}", first, second)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+pub(crate) fn middle_page_topological_sum(ordered: bool) -> anyhow::Result<usize> {
+    let (rules, updates) = read_rules_and_updates()?;
+    topological_sum(&rules, &updates, ordered)
+}
+
+fn topological_sum(
+    rules: &[[usize; 2]],
+    updates: &[Vec<usize>],
+    ordered: bool,
+) -> anyhow::Result<usize> {
+    let rules = hash_rules(rules.iter().copied());
+    updates.iter().try_fold(0, |sum, update| {
+        let sorted_update = topological_order(&rules, update)
+            .with_context(|| format!("Failed to order update {}", update.iter().join(",")))?;
+        let is_ordered = sorted_update == *update;
+        if is_ordered == ordered {
+            let mid = sorted_update.len() / 2;
+            return Ok(sum + sorted_update[mid]);
+        }
+        Ok(sum)
+    })
+}
+
+// Kahn's algorithm over the rules restricted to the pages of the update. More than one page
+// ready at the same time means the rules do not define a total order, and pages left over
+// when no page is ready are part of a cycle.
+fn topological_order(
+    rules: &HashMap<usize, HashSet<usize>>,
+    update: &[usize],
+) -> Result<Vec<usize>, OrderError> {
+    let pages: Vec<usize> = update.iter().copied().unique().collect();
+    let mut in_degrees: HashMap<usize, usize> = pages.iter().map(|&page| (page, 0)).collect();
+    pages.iter().for_each(|page| {
+        if let Some(successors) = rules.get(page) {
+            successors.iter().for_each(|successor| {
+                in_degrees.entry(*successor).and_modify(|d| *d += 1);
+            });
+        }
+    });
+
+    let mut ready: Vec<usize> = pages
+        .iter()
+        .copied()
+        .filter(|page| in_degrees[page] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(pages.len());
+    while let Some(page) = ready.pop() {
+        if let Some(&other) = ready.last() {
+            return Err(OrderError::Ambiguous(other.min(page), other.max(page)));
+        }
+        order.push(page);
+        if let Some(successors) = rules.get(&page) {
+            successors.iter().for_each(|successor| {
+                if let Some(d) = in_degrees.get_mut(successor) {
+                    *d -= 1;
+                    if *d == 0 {
+                        ready.push(*successor);
+                    }
+                }
+            });
+        }
+    }
+    if order.len() == pages.len() {
+        return Ok(order);
+    }
+
+    // Every page left has a predecessor that is also left, so walking predecessors
+    // eventually revisits a page.
+    let remaining: Vec<usize> = pages
+        .into_iter()
+        .filter(|page| in_degrees[page] > 0)
+        .collect();
+    let mut path = vec![remaining[0]];
+    while let Some(predecessor) = path.last().and_then(|&current| {
+        remaining
+            .iter()
+            .copied()
+            .find(|p| rules.get(p).is_some_and(|successors| successors.contains(&current)))
+    }) {
+        if let Some(start) = path.iter().position(|&page| page == predecessor) {
+            let mut cycle = path.split_off(start);
+            cycle.reverse();
+            return Err(OrderError::Cycle(cycle));
+        }
+        path.push(predecessor);
+    }
+    Err(OrderError::Cycle(path))
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Violation {
+    pub(crate) rule: [usize; 2],
+    pub(crate) positions: [usize; 2],
+}
+
+// Moving a page means taking it out of the update and reinserting it at `to` once every other
+// move has been taken out, in increasing order of `to`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Move {
+    pub(crate) page: usize,
+    pub(crate) from: usize,
+    pub(crate) to: usize,
+}
+
+// The violations are always listed, the moves only when the rules order every page of the update.
+#[derive(Debug, PartialEq)]
+pub(crate) struct UpdateDiagnostic {
+    pub(crate) update: Vec<usize>,
+    pub(crate) violations: Vec<Violation>,
+    pub(crate) moves: Result<Vec<Move>, OrderError>,
+}
+
+impl fmt::Display for UpdateDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Update {}", self.update.iter().join(","))?;
+        for Violation {
+            rule: [first, second],
+            positions: [first_pos, second_pos],
+        } in &self.violations
+        {
+            writeln!(
+                f,
+                "  rule {}|{} violated: {} at {} comes after {} at {}",
+                first, second, first, first_pos, second, second_pos
+            )?;
+        }
+        match &self.moves {
+            Ok(moves) => {
+                for Move { page, from, to } in moves {
+                    writeln!(f, "  move {} from {} to {}", page, from, to)?;
+                }
+            }
+            Err(e) => writeln!(f, "  cannot be reordered: {}", e)?,
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn explain_unordered_updates() -> anyhow::Result<Vec<UpdateDiagnostic>> {
+    let (rules, updates) = read_rules_and_updates()?;
+    let rules = hash_rules(rules.into_iter());
+    Ok(updates
+        .iter()
+        .filter_map(|update| diagnose_update(&rules, update))
+        .collect())
+}
+
+fn diagnose_update(
+    rules: &HashMap<usize, HashSet<usize>>,
+    update: &[usize],
+) -> Option<UpdateDiagnostic> {
+    let violations: Vec<Violation> = update
+        .iter()
+        .enumerate()
+        .tuple_combinations()
+        .filter(|((_, before), (_, after))| {
+            rules
+                .get(after)
+                .is_some_and(|successors| successors.contains(before))
+        })
+        .map(|((before_pos, &before), (after_pos, &after))| Violation {
+            rule: [after, before],
+            positions: [after_pos, before_pos],
+        })
+        .collect();
+    if violations.is_empty() {
+        return None;
+    }
+
+    Some(UpdateDiagnostic {
+        update: update.to_vec(),
+        violations,
+        moves: topological_order(rules, update).map(|sorted_update| moves(update, &sorted_update)),
+    })
+}
+
+fn moves(update: &[usize], sorted_update: &[usize]) -> Vec<Move> {
+    let ranks: HashMap<usize, usize> = sorted_update
+        .iter()
+        .enumerate()
+        .map(|(rank, &page)| (page, rank))
+        .collect();
+    let kept = longest_increasing_subsequence(&update.iter().map(|page| ranks[page]).collect_vec());
+    update
+        .iter()
+        .enumerate()
+        .filter(|(from, _)| !kept.contains(from))
+        .map(|(from, &page)| Move {
+            page,
+            from,
+            to: ranks[&page],
+        })
+        .sorted_by_key(|m| m.to)
+        .collect()
+}
+
+// Pages outside the longest subsequence already in sorted order are the fewest that must move.
+fn longest_increasing_subsequence(values: &[usize]) -> HashSet<usize> {
+    let mut lengths = vec![1; values.len()];
+    let mut previous = vec![None; values.len()];
+    for i in 0..values.len() {
+        for j in 0..i {
+            if values[j] < values[i] && lengths[j] + 1 > lengths[i] {
+                lengths[i] = lengths[j] + 1;
+                previous[i] = Some(j);
+            }
+        }
+    }
+    let mut kept = HashSet::new();
+    let mut current = lengths.iter().position_max();
+    while let Some(i) = current {
+        kept.insert(i);
+        current = previous[i];
+    }
+    kept
+}
+
+type Strategy = fn(&[[usize; 2]], &[Vec<usize>], bool) -> anyhow::Result<Option<usize>>;
+
+// Brute force only checks whether an update is already ordered, so it has no unordered answer.
+const STRATEGIES: [(&str, Strategy); 4] = [
+    ("brute force", |rules, updates, ordered| {
+        Ok(ordered.then(|| brute_force_sum(rules, updates)))
+    }),
+    ("predecessors", |rules, updates, ordered| {
+        Ok(Some(predecessors_sum(rules, updates, ordered)))
+    }),
+    ("custom order", |rules, updates, ordered| {
+        Ok(Some(custom_order_sum(rules, updates, ordered)))
+    }),
+    ("topological sort", |rules, updates, ordered| {
+        topological_sum(rules, updates, ordered).map(Some)
+    }),
+];
+
+// Strategies that give a sum disagree when their sums differ. Strategies that fail are listed
+// too, but only disagree on their own when the rules order every update.
+#[derive(Debug)]
+pub(crate) struct Disagreement {
+    pub(crate) rules: Vec<[usize; 2]>,
+    pub(crate) updates: Vec<Vec<usize>>,
+    pub(crate) ordered: bool,
+    pub(crate) sums: Vec<(&'static str, usize)>,
+    pub(crate) errors: Vec<(&'static str, anyhow::Error)>,
+}
+
+impl fmt::Display for Disagreement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.ordered { "ordered" } else { "unordered" };
+        writeln!(f, "Strategies disagree on the {} sum:", kind)?;
+        for (name, sum) in &self.sums {
+            writeln!(f, "  {}: {}", name, sum)?;
+        }
+        for (name, error) in &self.errors {
+            writeln!(f, "  {}: {:#}", name, error)?;
+        }
+        writeln!(f)?;
+        for [first, second] in &self.rules {
+            writeln!(f, "{}|{}", first, second)?;
+        }
+        writeln!(f)?;
+        for update in &self.updates {
+            writeln!(f, "{}", update.iter().join(","))?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn find_disagreement(
+    rule_set: RuleSet,
+    cases: usize,
+    seed: u64,
+) -> Option<Disagreement> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..cases).find_map(|_| {
+        let (rules, updates) = generate_rules_and_updates(&mut rng, rule_set);
+        compare_strategies(&STRATEGIES, rules, updates)
+    })
+}
+
+fn compare_strategies(
+    strategies: &[(&'static str, Strategy)],
+    rules: Vec<[usize; 2]>,
+    updates: Vec<Vec<usize>>,
+) -> Option<Disagreement> {
+    for ordered in [true, false] {
+        let (mut sums, mut errors) = (vec![], vec![]);
+        for (name, strategy) in strategies {
+            match strategy(&rules, &updates, ordered) {
+                Ok(Some(sum)) => sums.push((*name, sum)),
+                Ok(None) => (),
+                Err(e) => errors.push((*name, e)),
+            }
+        }
+        let unexpected_errors = !errors.is_empty() && orders_every_update(&rules, &updates);
+        if unexpected_errors || !sums.iter().map(|(_, sum)| sum).all_equal() {
+            return Some(Disagreement {
+                rules,
+                updates,
+                ordered,
+                sums,
+                errors,
+            });
+        }
+    }
+    None
+}
+
+// Each pair of pages of an update must be ordered by exactly one rule, and without cycles every
+// page then comes before a different number of the others.
+fn orders_every_update(rules: &[[usize; 2]], updates: &[Vec<usize>]) -> bool {
+    let rules = hash_rules(rules.iter().copied());
+    let precedes = |first, second| rules.get(&first).is_some_and(|s| s.contains(&second));
+    updates.iter().all(|update| {
+        let pages = update.iter().copied().unique().collect_vec();
+        pages
+            .iter()
+            .tuple_combinations()
+            .all(|(&first, &second)| precedes(first, second) != precedes(second, first))
+            && pages
+                .iter()
+                .map(|&page| pages.iter().filter(|&&other| precedes(page, other)).count())
+                .all_unique()
+    })
+}
+
+// `Total` rules cover every pair of pages so that each update has exactly one valid order, as in
+// the puzzle input. `Sparse` rules leave some pairs of that order out, so pages can tie, and
+// `Cyclic` rules reverse some of them, so pages can form cycles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RuleSet {
+    Total,
+    Sparse,
+    Cyclic,
+}
+
+impl FromStr for RuleSet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "total" => Ok(RuleSet::Total),
+            "sparse" => Ok(RuleSet::Sparse),
+            "cyclic" => Ok(RuleSet::Cyclic),
+            _ => bail!("Unknown rule set {}", s),
+        }
+    }
+}
+
+fn generate_rules_and_updates(
+    rng: &mut StdRng,
+    rule_set: RuleSet,
+) -> (Vec<[usize; 2]>, Vec<Vec<usize>>) {
+    let mut pages = (10..100).collect_vec();
+    pages.shuffle(rng);
+    pages.truncate(rng.gen_range(1..25));
+    let mut rules = pages
+        .iter()
+        .tuple_combinations()
+        .map(|(&first, &second)| [first, second])
+        .collect_vec();
+    match rule_set {
+        RuleSet::Total => (),
+        RuleSet::Sparse => rules.retain(|_| rng.gen_bool(0.8)),
+        RuleSet::Cyclic => rules
+            .iter_mut()
+            .filter(|_| rng.gen_bool(0.05))
+            .for_each(|rule| rule.reverse()),
+    }
+    rules.shuffle(rng);
+    let updates = (0..rng.gen_range(1..10))
+        .map(|_| {
+            let mut update = pages.clone();
+            update.shuffle(rng);
+            update.truncate(rng.gen_range(1..=pages.len()));
+            update
+        })
+        .collect();
+    (rules, updates)
 }
 
 #[cfg(test)]
@@ -190,6 +597,7 @@ mod test {
         assert_eq!(middle_page_ordered_updates_sum()?, expected);
         assert_eq!(middle_page_sum(true)?, expected);
         assert_eq!(page_custom_order(true)?, expected);
+        assert_eq!(middle_page_topological_sum(true)?, expected);
         Ok(())
     }
 
@@ -198,6 +606,123 @@ mod test {
         let expected = 4260;
         assert_eq!(middle_page_sum(false)?, expected);
         assert_eq!(page_custom_order(false)?, expected);
+        assert_eq!(middle_page_topological_sum(false)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strategies_agree() -> anyhow::Result<()> {
+        if let Some(disagreement) = find_disagreement(RuleSet::Total, 500, 5) {
+            panic!("{}", disagreement);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_inconsistent_rules_disagree() -> anyhow::Result<()> {
+        // The topological sort fails on these rules, but the other strategies still disagree.
+        // Which of the tied pages predecessors keeps depends on the hash map order.
+        for rule_set in [RuleSet::Sparse, RuleSet::Cyclic] {
+            let disagreement = find_disagreement(rule_set, 500, 5).context("No disagreement")?;
+            assert!(!disagreement.ordered);
+            let names = disagreement.sums.iter().map(|(name, _)| *name).collect_vec();
+            assert_eq!(names, vec!["predecessors", "custom order"]);
+            assert_ne!(disagreement.sums[0].1, disagreement.sums[1].1);
+            let failed = disagreement.errors.iter().map(|(name, _)| *name).collect_vec();
+            assert_eq!(failed, vec!["topological sort"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_reported() -> anyhow::Result<()> {
+        let failing: Strategy = |_, _, _| anyhow::bail!("Cannot order");
+        let strategies = [STRATEGIES[1], ("failing", failing)];
+        let disagreement = compare_strategies(&strategies, vec![[1, 2]], vec![vec![2, 1]])
+            .context("No disagreement")?;
+        assert_eq!(disagreement.sums, vec![("predecessors", 0)]);
+        assert_eq!(disagreement.errors.len(), 1);
+        assert!(compare_strategies(&strategies, vec![[1, 2]], vec![vec![1, 3]]).is_none());
+        assert!(!orders_every_update(&[[1, 2], [2, 3], [3, 1]], &[vec![1, 2, 3]]));
+        assert!(orders_every_update(&[[1, 2], [2, 3], [1, 3]], &[vec![3, 1, 2]]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_disagreement_reported() -> anyhow::Result<()> {
+        let always_ordered: Strategy =
+            |rules, updates, _| Ok(Some(custom_order_sum(rules, updates, true)));
+        let strategies = [STRATEGIES[1], ("always ordered", always_ordered)];
+        let disagreement = compare_strategies(&strategies, vec![[1, 2]], vec![vec![2, 1, 3]])
+            .context("No disagreement")?;
+        assert!(!disagreement.ordered);
+        assert_eq!(disagreement.sums, vec![("predecessors", 1), ("always ordered", 0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_topological_order_errors() {
+        let rules = hash_rules(create_rules("1|2\n2|3\n3|1\n4|5"));
+        assert_eq!(topological_order(&rules, &[4, 5]), Ok(vec![4, 5]));
+        assert_eq!(
+            topological_order(&rules, &[4, 1, 2, 3]),
+            Err(OrderError::Cycle(vec![2, 3, 1]))
+        );
+        assert_eq!(
+            topological_order(&rules, &[5, 1]),
+            Err(OrderError::Ambiguous(1, 5))
+        );
+    }
+
+    #[test]
+    fn test_diagnose_update() -> anyhow::Result<()> {
+        let rules = hash_rules(create_rules(
+            "47|53\n97|13\n97|61\n97|47\n75|29\n61|13\n75|53\n29|13\n97|29\n53|29\n61|53\n\
+             97|53\n61|29\n47|13\n75|47\n97|75\n47|61\n75|61\n47|29\n75|13\n53|13",
+        ));
+        assert_eq!(diagnose_update(&rules, &[75, 47, 61, 53, 29]), None);
+
+        let diagnostic = diagnose_update(&rules, &[97, 13, 75, 29, 47]).context("Ordered")?;
+        assert_eq!(diagnostic.violations.len(), 4);
+        assert!(diagnostic.violations.contains(&Violation {
+            rule: [29, 13],
+            positions: [3, 1]
+        }));
+        let moves = diagnostic.moves?;
+        assert_eq!(
+            moves,
+            vec![
+                Move { page: 29, from: 3, to: 3 },
+                Move { page: 13, from: 1, to: 4 },
+            ]
+        );
+
+        let mut fixed = diagnostic.update.clone();
+        moves.iter().for_each(|m| fixed.retain(|&page| page != m.page));
+        moves.iter().for_each(|m| fixed.insert(m.to, m.page));
+        assert_eq!(fixed, vec![97, 75, 47, 29, 13]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnose_unorderable_update() -> anyhow::Result<()> {
+        let rules = hash_rules(create_rules("1|2\n2|3\n3|1\n4|5"));
+        let diagnostic = diagnose_update(&rules, &[2, 1, 3]).context("Ordered")?;
+        assert_eq!(
+            diagnostic.violations,
+            vec![
+                Violation { rule: [1, 2], positions: [1, 0] },
+                Violation { rule: [3, 1], positions: [2, 1] },
+            ]
+        );
+        assert_eq!(diagnostic.moves, Err(OrderError::Cycle(vec![3, 1, 2])));
+
+        let diagnostic = diagnose_update(&rules, &[5, 4, 1]).context("Ordered")?;
+        assert_eq!(
+            diagnostic.violations,
+            vec![Violation { rule: [4, 5], positions: [1, 0] }]
+        );
+        assert_eq!(diagnostic.moves, Err(OrderError::Ambiguous(1, 4)));
         Ok(())
     }
 }
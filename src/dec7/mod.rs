@@ -1,83 +1,461 @@
 use std::{
+    fmt,
     fs::File,
     io::{BufRead, BufReader},
+    num::ParseIntError,
+    str::FromStr,
 };
 
 use rayon::prelude::*;
 use anyhow::Context;
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
+use num_traits::{PrimInt, Unsigned};
 
 const PATH: &str = "src/dec7/input.txt";
 
-pub(crate) fn feasible_equations() -> anyhow::Result<usize> {
+pub(crate) const PART_ONE: &[Operation] = &[Operation::Sum, Operation::Multiplication];
+pub(crate) const PART_TWO: &[Operation] =
+    &[Operation::Sum, Operation::Multiplication, Operation::Union];
+
+// Every operation is checked, so a result that does not fit in the operand type discards that
+// branch instead of wrapping. Use `u128` for inputs wider than `usize`.
+pub(crate) trait Operand:
+    PrimInt + Unsigned + FromStr<Err = ParseIntError> + fmt::Display + Send + Sync
+{
+}
+
+impl<N> Operand for N where
+    N: PrimInt + Unsigned + FromStr<Err = ParseIntError> + fmt::Display + Send + Sync
+{
+}
+
+pub(crate) fn feasible_equations<N: Operand>(operations: &[Operation]) -> anyhow::Result<N> {
+    feasible_equations_sum(|equation: &Equation<N>| equation.is_feasible(operations))
+}
+
+pub(crate) fn feasible_equations_reverse<N: Operand>(
+    operations: &[Operation],
+) -> anyhow::Result<N> {
+    feasible_equations_sum(|equation: &Equation<N>| equation.is_feasible_reverse(operations))
+}
+
+fn feasible_equations_sum<N, F>(is_feasible: F) -> anyhow::Result<N>
+where
+    N: Operand,
+    F: Fn(&Equation<N>) -> bool + Sync,
+{
     let file = File::open(PATH)?;
     let content = BufReader::new(file);
 
-    let equations_sum = content
+    content
         .lines()
         .map_while(Result::ok)
         .par_bridge()
-        .flat_map(get_target_and_operands)
-        .map(|(target, operands)| {
-            if Operation::iter()
-                .any(|operation| recursive_operate_to_target(target, operands[0], operation, &operands[1..]))
-            {
-                target
+        .map(|line| {
+            let (target, operands) = get_target_and_operands(line)?;
+            if is_feasible(&Equation { target, operands }) {
+                Ok(target)
             } else {
-                0
+                Ok(N::zero())
             }
         })
-        .sum();
-    Ok(equations_sum)
+        .try_reduce(N::zero, |a, b| {
+            a.checked_add(&b).context("Feasible equations sum overflows")
+        })
 }
 
-fn get_target_and_operands(line: String) -> anyhow::Result<(usize, Vec<usize>)> {
+pub(crate) struct Equation<N = usize> {
+    pub(crate) target: N,
+    pub(crate) operands: Vec<N>,
+}
+
+pub(crate) struct Solution<'a, N> {
+    equation: &'a Equation<N>,
+    operations: Vec<Operation>,
+}
+
+impl<N: Operand> fmt::Display for Solution<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Equation { target, operands } = self.equation;
+        write!(f, "{} = {}", target, operands[0])?;
+        for (operation, operand) in self.operations.iter().zip(&operands[1..]) {
+            write!(f, " {} {}", operation, operand)?;
+        }
+        Ok(())
+    }
+}
+
+impl<N: Operand> Equation<N> {
+    pub(crate) fn is_feasible(&self, operations: &[Operation]) -> bool {
+        if self.operands.len() < 2 {
+            return false;
+        }
+        let can_prune = can_prune(operations, &self.operands[1..]);
+        operations.iter().any(|&operation| {
+            recursive_operate_to_target(
+                self.target,
+                self.operands[0],
+                operation,
+                &self.operands[1..],
+                operations,
+                can_prune,
+            )
+        })
+    }
+
+    // Undoing a multiplication by zero could lead to any previous value, so those equations are
+    // left to the forward search.
+    pub(crate) fn is_feasible_reverse(&self, operations: &[Operation]) -> bool {
+        if self.operands.contains(&N::zero()) {
+            return self.is_feasible(operations);
+        }
+        self.operands.len() > 1 && recursive_undo_to_start(self.target, &self.operands, operations)
+    }
+
+    pub(crate) fn solutions(
+        &self,
+        operations: &[Operation],
+        first_only: bool,
+    ) -> Vec<Solution<'_, N>> {
+        let mut solutions = vec![];
+        if self.operands.len() > 1 {
+            collect_operations(
+                self.target,
+                self.operands[0],
+                &self.operands[1..],
+                operations,
+                &mut vec![],
+                &mut solutions,
+                first_only,
+            );
+        }
+        solutions
+            .into_iter()
+            .map(|operations| Solution {
+                equation: self,
+                operations,
+            })
+            .collect()
+    }
+
+    pub(crate) fn solution_count(&self, operations: &[Operation]) -> usize {
+        if self.operands.len() > 1 {
+            count_operations(self.target, self.operands[0], &self.operands[1..], operations)
+        } else {
+            0
+        }
+    }
+}
+
+pub(crate) fn read_equations<N: Operand>() -> anyhow::Result<Vec<Equation<N>>> {
+    let file = File::open(PATH)?;
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let (target, operands) = get_target_and_operands(line?)?;
+            Ok(Equation { target, operands })
+        })
+        .collect()
+}
+
+fn get_target_and_operands<N: Operand>(line: String) -> anyhow::Result<(N, Vec<N>)> {
     let mut split = line.split(": ");
     let target = split.next().context("No target")?.parse()?;
     let operand: Result<_, _> = split
         .next()
         .context("No operands")?
         .split(" ")
-        .map(|n| n.parse::<usize>())
+        .map(|n| n.parse::<N>())
         .collect();
     Ok((target, operand?))
 }
 
-fn recursive_operate_to_target(
-    target: usize,
-    curr: usize,
+// Pruning on the running value exceeding the target is only valid if no operation on the
+// remaining operands can bring it back down.
+fn can_prune<N: Operand>(operations: &[Operation], rem: &[N]) -> bool {
+    operations
+        .iter()
+        .all(|operation| rem.iter().all(|&b| operation.never_decreases(b)))
+}
+
+fn recursive_operate_to_target<N: Operand>(
+    target: N,
+    curr: N,
     operation: Operation,
-    rem: &[usize],
+    rem: &[N],
+    operations: &[Operation],
+    can_prune: bool,
 ) -> bool {
     if rem.is_empty() {
         return false;
     }
-    let new_curr = operation.operate(curr, rem[0]);
-    if new_curr > target {
+    let Some(new_curr) = operation.operate(curr, rem[0]) else {
+        return false;
+    };
+    if can_prune && new_curr > target {
         return false;
     }
     if new_curr == target && rem.len() == 1 {
         return true;
     }
     let new_rem = &rem[1..];
-    Operation::iter()
-        .any(|operation| recursive_operate_to_target(target, new_curr, operation, new_rem))
+    operations.iter().any(|&operation| {
+        recursive_operate_to_target(target, new_curr, operation, new_rem, operations, can_prune)
+    })
+}
+
+fn recursive_undo_to_start<N: Operand>(target: N, rem: &[N], operations: &[Operation]) -> bool {
+    match rem.split_last() {
+        Some((&first, [])) => target == first,
+        Some((&last, new_rem)) => operations.iter().any(|operation| {
+            operation
+                .undo(target, last)
+                .is_some_and(|new_target| recursive_undo_to_start(new_target, new_rem, operations))
+        }),
+        None => false,
+    }
+}
+
+fn collect_operations<N: Operand>(
+    target: N,
+    curr: N,
+    rem: &[N],
+    operations: &[Operation],
+    chosen: &mut Vec<Operation>,
+    solutions: &mut Vec<Vec<Operation>>,
+    first_only: bool,
+) {
+    if rem.is_empty() {
+        if curr == target {
+            solutions.push(chosen.clone());
+        }
+        return;
+    }
+    let can_prune = can_prune(operations, rem);
+    for &operation in operations {
+        if first_only && !solutions.is_empty() {
+            return;
+        }
+        let Some(new_curr) = operation.operate(curr, rem[0]) else {
+            continue;
+        };
+        if can_prune && new_curr > target {
+            continue;
+        }
+        chosen.push(operation);
+        collect_operations(target, new_curr, &rem[1..], operations, chosen, solutions, first_only);
+        chosen.pop();
+    }
 }
 
-#[derive(EnumIter)]
-enum Operation {
+fn count_operations<N: Operand>(target: N, curr: N, rem: &[N], operations: &[Operation]) -> usize {
+    if rem.is_empty() {
+        return (curr == target) as usize;
+    }
+    let can_prune = can_prune(operations, rem);
+    operations
+        .iter()
+        .filter_map(|operation| operation.operate(curr, rem[0]))
+        .filter(|&new_curr| !can_prune || new_curr <= target)
+        .map(|new_curr| count_operations(target, new_curr, &rem[1..], operations))
+        .sum()
+}
+
+// `BaseUnion` concatenates the digits of both operands written in the given base.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Operation {
     Sum,
     Multiplication,
     Union,
+    Subtraction,
+    BaseUnion(usize),
 }
 
 impl Operation {
-    fn operate(self, a: usize, b: usize) -> usize {
+    fn operate<N: Operand>(self, a: N, b: N) -> Option<N> {
+        match self {
+            Self::Multiplication => a.checked_mul(&b),
+            Self::Sum => a.checked_add(&b),
+            Self::Union => a.checked_mul(&digit_shift(b, 10)?)?.checked_add(&b),
+            Self::Subtraction => a.checked_sub(&b),
+            Self::BaseUnion(base) => a.checked_mul(&digit_shift(b, base)?)?.checked_add(&b),
+        }
+    }
+
+    // Returns the left operand `a` such that `a op b == result`, if there is one.
+    fn undo<N: Operand>(self, result: N, b: N) -> Option<N> {
+        match self {
+            Self::Multiplication => (!b.is_zero() && (result % b).is_zero()).then(|| result / b),
+            Self::Sum => result.checked_sub(&b),
+            Self::Union => strip_digits(result, b, 10),
+            Self::Subtraction => result.checked_add(&b),
+            Self::BaseUnion(base) => strip_digits(result, b, base),
+        }
+    }
+
+    // Whether `a op b >= a` for every `a`.
+    fn never_decreases<N: Operand>(&self, b: N) -> bool {
+        match self {
+            Self::Subtraction => b.is_zero(),
+            Self::Multiplication => !b.is_zero(),
+            _ => true,
+        }
+    }
+}
+
+// Smallest power of `base` greater than `b`, i.e. the factor that makes room for the digits of `b`.
+// Counting digits on integers keeps `b == 0` as a single digit. Bases below 2 have no digits.
+fn digit_shift<N: Operand>(b: N, base: usize) -> Option<N> {
+    if base < 2 {
+        return None;
+    }
+    let base = N::from(base)?;
+    let mut shift = base;
+    while shift <= b {
+        shift = shift.checked_mul(&base)?;
+    }
+    Some(shift)
+}
+
+fn strip_digits<N: Operand>(result: N, b: N, base: usize) -> Option<N> {
+    let shift = digit_shift(b, base)?;
+    (result % shift == b).then(|| result / shift)
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Multiplication => a * b,
-            Self::Sum => a + b,
-            Self::Union => a * 10usize.pow((b as f64).log10().floor() as u32 + 1) + b,
+            Self::Sum => write!(f, "+"),
+            Self::Multiplication => write!(f, "*"),
+            Self::Union => write!(f, "||"),
+            Self::Subtraction => write!(f, "-"),
+            Self::BaseUnion(base) => write!(f, "||{}", base),
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_equation(line: &str) -> anyhow::Result<Equation> {
+        let (target, operands) = get_target_and_operands(line.to_string())?;
+        Ok(Equation { target, operands })
+    }
+
+    #[test]
+    fn test_solutions() -> anyhow::Result<()> {
+        let equation = parse_equation("292: 11 6 16 20")?;
+        let solutions = equation.solutions(PART_ONE, false);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].to_string(), "292 = 11 + 6 * 16 + 20");
+        assert_eq!(equation.solution_count(PART_ONE), 1);
+        assert_eq!(equation.solution_count(PART_TWO), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_solution_count() -> anyhow::Result<()> {
+        let equation = parse_equation("3267: 81 40 27")?;
+        assert_eq!(equation.solution_count(PART_TWO), 2);
+        assert_eq!(equation.solutions(PART_TWO, true).len(), 1);
+        assert_eq!(
+            equation.solutions(PART_TWO, false).iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            vec!["3267 = 81 + 40 * 27", "3267 = 81 * 40 + 27"]
+        );
+        assert_eq!(
+            parse_equation("7290: 6 8 6 15")?.solutions(PART_TWO, true)[0].to_string(),
+            "7290 = 6 * 8 || 6 * 15"
+        );
+        assert_eq!(parse_equation("7290: 6 8 6 15")?.solution_count(PART_ONE), 0);
+        assert_eq!(parse_equation("83: 17 5")?.solution_count(PART_TWO), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reverse_matches_forward() -> anyhow::Result<()> {
+        let custom = [Operation::Sum, Operation::Subtraction, Operation::BaseUnion(3)];
+        for operations in [PART_ONE, PART_TWO, &custom] {
+            for equation in read_equations::<usize>()? {
+                assert_eq!(
+                    equation.is_feasible(operations),
+                    equation.is_feasible_reverse(operations)
+                );
+            }
+        }
+        assert_eq!(
+            feasible_equations_reverse::<usize>(PART_TWO)?,
+            feasible_equations::<usize>(PART_TWO)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_checked_operations() -> anyhow::Result<()> {
+        assert_eq!(Operation::Union.operate(12usize, 0), Some(120));
+        assert_eq!(Operation::Union.operate(12usize, 10), Some(1210));
+        assert_eq!(Operation::Union.operate(12usize, 9), Some(129));
+        assert_eq!(Operation::Union.undo(1210usize, 10), Some(12));
+        assert_eq!(Operation::Union.operate(usize::MAX / 10, 10), None);
+        assert_eq!(Operation::Multiplication.operate(usize::MAX, 2), None);
+
+        let line = "100000000000000000000: 10000000000 10000000000";
+        assert!(get_target_and_operands::<usize>(line.to_string()).is_err());
+        let (target, operands) = get_target_and_operands::<u128>(line.to_string())?;
+        let equation = Equation { target, operands };
+        assert!(equation.is_feasible(PART_ONE));
+        assert!(equation.is_feasible_reverse(PART_ONE));
+        assert_eq!(feasible_equations::<u128>(PART_TWO)?, 223472064194845);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_operations() -> anyhow::Result<()> {
+        let operations = [Operation::Sum, Operation::Subtraction, Operation::BaseUnion(2)];
+        let equation = parse_equation("13: 20 8 3 2")?;
+        let solutions = equation.solutions(&operations, false);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].to_string(), "13 = 20 - 8 + 3 - 2");
+        let equation = parse_equation("14: 3 2")?;
+        assert_eq!(equation.solutions(&operations, true)[0].to_string(), "14 = 3 ||2 2");
+        assert_eq!(equation.solution_count(PART_TWO), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_bases() -> anyhow::Result<()> {
+        for base in [0, 1] {
+            let operations = [Operation::Sum, Operation::BaseUnion(base)];
+            assert_eq!(Operation::BaseUnion(base).operate(12usize, 3), None);
+            assert_eq!(Operation::BaseUnion(base).undo(123usize, 3), None);
+            let equation = parse_equation("123: 12 3")?;
+            assert!(!equation.is_feasible(&operations));
+            assert!(!equation.is_feasible_reverse(&operations));
+            assert_eq!(equation.solution_count(&operations), 0);
+            assert!(parse_equation("15: 12 3")?.is_feasible(&operations));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_operands() -> anyhow::Result<()> {
+        let equation = parse_equation("5: 3 4 0 5")?;
+        assert!(equation.is_feasible(PART_ONE));
+        assert!(equation.is_feasible_reverse(PART_ONE));
+        assert_eq!(equation.solution_count(PART_ONE), 2);
+        assert_eq!(equation.solutions(PART_ONE, true)[0].to_string(), "5 = 3 + 4 * 0 + 5");
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_operands() {
+        for operands in [vec![], vec![5]] {
+            let equation = Equation {
+                target: 5usize,
+                operands,
+            };
+            assert!(!equation.is_feasible(PART_TWO));
+            assert!(!equation.is_feasible_reverse(PART_TWO));
+            assert_eq!(equation.solution_count(PART_TWO), 0);
+            assert!(equation.solutions(PART_TWO, false).is_empty());
         }
     }
 }
@@ -1,55 +1,167 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{BufRead, BufReader},
+    ops::RangeInclusive,
 };
 
-use derive_more::derive::Add;
 use itertools::Itertools;
 use num_integer::gcd;
 
 const PATH: &str = "src/dec8/input.txt";
 
+// Antinodes lie `k` steps beyond each antenna of a pair, away from the other one, for every `k`
+// in `multiples`, counted in `unit`. With `interior`, the grid points strictly between the
+// antennas are antinodes too.
+pub(crate) struct AntinodeRule {
+    pub(crate) multiples: RangeInclusive<usize>,
+    pub(crate) unit: StepUnit,
+    pub(crate) interior: bool,
+}
+
+// A step is either the whole distance between the antennas, or the smallest grid step along
+// their line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum StepUnit {
+    Distance,
+    Lattice,
+}
+
+pub(crate) const PART_ONE: AntinodeRule = AntinodeRule {
+    multiples: 1..=1,
+    unit: StepUnit::Distance,
+    interior: false,
+};
+
+pub(crate) const PART_TWO: AntinodeRule = AntinodeRule {
+    multiples: 0..=usize::MAX,
+    unit: StepUnit::Lattice,
+    interior: true,
+};
+
 pub(crate) fn count_antinodes() -> anyhow::Result<usize> {
-    let file = File::open(PATH)?;
-    let content = BufReader::new(file);
-    let mut antenna_positions: HashMap<char, Vec<Point>> = HashMap::new();
-    let mut antinode_positions = HashSet::new();
+    count_antinodes_with(&PART_ONE)
+}
 
-    content.lines().enumerate().try_for_each(|(i, line)| {
-        line?.chars().enumerate().for_each(|(j, char)| {
-            if char != '.' {
-                antenna_positions
-                    .entry(char)
-                    .and_modify(|positions| positions.push(Point(i, j)))
-                    .or_insert(vec![Point(i, j)]);
-            }
-        });
-        Ok::<(), io::Error>(())
-    })?;
+pub(crate) fn count_resonating_antinodes() -> anyhow::Result<usize> {
+    count_antinodes_with(&PART_TWO)
+}
 
-    antenna_positions.values().for_each(|positions| {
-        positions.iter().combinations(2).for_each(|combination| {
-            let (first, second) = (combination[0], combination[1]);
-            if let Some(antinode) = (*first + *first).try_sub(second) {
-                antinode_positions.insert(antinode);
-            }
-            if let Some(antinode) = (*second + *second).try_sub(first) {
-                antinode_positions.insert(antinode);
+pub(crate) fn count_antinodes_with(rule: &AntinodeRule) -> anyhow::Result<usize> {
+    let map = read_antenna_map(BufReader::new(File::open(PATH)?))?;
+    Ok(map.antinodes(rule).len())
+}
+
+pub(crate) fn render_antinodes(rule: &AntinodeRule) -> anyhow::Result<String> {
+    let map = read_antenna_map(BufReader::new(File::open(PATH)?))?;
+    Ok(map.render(&map.antinodes(rule)))
+}
+
+pub(crate) struct FrequencyReport {
+    pub(crate) frequency: char,
+    pub(crate) antinodes: usize,
+    pub(crate) overlaps: BTreeMap<(usize, usize), Vec<char>>,
+}
+
+impl fmt::Display for FrequencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} antinodes, {} shared",
+            self.frequency,
+            self.antinodes,
+            self.overlaps.len()
+        )?;
+        for ((i, j), frequencies) in &self.overlaps {
+            write!(f, " ({},{}) with {}", i, j, frequencies.iter().join(""))?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn antinodes_per_frequency(rule: &AntinodeRule) -> anyhow::Result<Vec<FrequencyReport>> {
+    let map = read_antenna_map(BufReader::new(File::open(PATH)?))?;
+    Ok(frequency_reports(&map.antinodes_by_frequency(rule)))
+}
+
+fn frequency_reports(antinodes: &HashMap<char, HashSet<Point>>) -> Vec<FrequencyReport> {
+    antinodes
+        .iter()
+        .sorted_by_key(|(&frequency, _)| frequency)
+        .map(|(&frequency, positions)| {
+            let overlaps = positions
+                .iter()
+                .filter_map(|point| {
+                    let others: Vec<char> = antinodes
+                        .iter()
+                        .filter(|(&other, other_positions)| {
+                            other != frequency && other_positions.contains(point)
+                        })
+                        .map(|(&other, _)| other)
+                        .sorted()
+                        .collect();
+                    (!others.is_empty()).then_some(((point.0, point.1), others))
+                })
+                .collect();
+            FrequencyReport {
+                frequency,
+                antinodes: positions.len(),
+                overlaps,
             }
         })
-    });
-    Ok(antinode_positions.len())
+        .collect()
 }
 
-pub(crate) fn count_resonating_antinodes() -> anyhow::Result<usize> {
-    let file = File::open(PATH)?;
-    let content = BufReader::new(file);
+struct AntennaMap {
+    height: usize,
+    width: usize,
+    antenna_positions: HashMap<char, Vec<Point>>,
+}
+
+impl AntennaMap {
+    fn contains(&self, i: isize, j: isize) -> bool {
+        (0..self.height as isize).contains(&i) && (0..self.width as isize).contains(&j)
+    }
+
+    fn antinodes(&self, rule: &AntinodeRule) -> HashSet<Point> {
+        self.antinodes_by_frequency(rule).into_values().flatten().collect()
+    }
+
+    fn antinodes_by_frequency(&self, rule: &AntinodeRule) -> HashMap<char, HashSet<Point>> {
+        self.antenna_positions
+            .iter()
+            .map(|(&frequency, positions)| {
+                let mut antinode_positions = HashSet::new();
+                positions.iter().combinations(2).for_each(|combination| {
+                    let (first, second) = (combination[0], combination[1]);
+                    antinode_positions.extend(first.calculate_antinodes(second, rule, self));
+                });
+                (frequency, antinode_positions)
+            })
+            .collect()
+    }
+
+    // Antennas are drawn over the antinodes that share their position.
+    fn render(&self, antinodes: &HashSet<Point>) -> String {
+        let mut grid = vec![vec!['.'; self.width]; self.height];
+        antinodes.iter().for_each(|Point(i, j)| grid[*i][*j] = '#');
+        self.antenna_positions.iter().for_each(|(&frequency, positions)| {
+            positions.iter().for_each(|Point(i, j)| grid[*i][*j] = frequency)
+        });
+        grid.iter().map(|row| row.iter().collect::<String>()).join("\n")
+    }
+}
+
+fn read_antenna_map<R: BufRead>(content: R) -> anyhow::Result<AntennaMap> {
     let mut antenna_positions: HashMap<char, Vec<Point>> = HashMap::new();
-    let mut antinode_positions = HashSet::new();
+    let (mut height, mut width) = (0, 0);
 
     content.lines().enumerate().try_for_each(|(i, line)| {
-        line?.chars().enumerate().for_each(|(j, char)| {
+        let line = line?;
+        height = i + 1;
+        width = width.max(line.chars().count());
+        line.chars().enumerate().for_each(|(j, char)| {
             if char != '.' {
                 antenna_positions
                     .entry(char)
@@ -57,48 +169,153 @@ pub(crate) fn count_resonating_antinodes() -> anyhow::Result<usize> {
                     .or_insert(vec![Point(i, j)]);
             }
         });
-        Ok::<(), io::Error>(())
+        Ok::<(), anyhow::Error>(())
     })?;
 
-    antenna_positions.values().for_each(|positions| {
-        positions.iter().combinations(2).for_each(|combination| {
-            let (first, second) = (combination[0], combination[1]);
-            antinode_positions.extend(first.calculate_line_points(second));
-        })
-    });
-    Ok(antinode_positions.len())
+    Ok(AntennaMap {
+        height,
+        width,
+        antenna_positions,
+    })
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Add, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 struct Point(usize, usize);
 
 impl Point {
-    fn try_sub(&self, rhs: &Self) -> Option<Self> {
-        match (self.0.checked_sub(rhs.0), self.1.checked_sub(rhs.1)) {
-            (Some(i), Some(j)) if i < 50 && j < 50 => Some(Point(i, j)),
-            _ => None,
-        }
-    }
-
-    fn calculate_line_points(&self, rhs: &Self) -> Vec<Self> {
+    fn calculate_antinodes(&self, rhs: &Self, rule: &AntinodeRule, map: &AntennaMap) -> Vec<Self> {
         let (i_diff, j_diff) = (
             self.0 as isize - rhs.0 as isize,
             self.1 as isize - rhs.1 as isize,
         );
-        let denominator = gcd(i_diff, j_diff);
-        let (i_min, j_min) = (i_diff / denominator, j_diff / denominator);
+        let lattice_steps = gcd(i_diff, j_diff);
+        let (i_lattice, j_lattice) = (i_diff / lattice_steps, j_diff / lattice_steps);
+        let (i_step, j_step) = match rule.unit {
+            StepUnit::Distance => (i_diff, j_diff),
+            StepUnit::Lattice => (i_lattice, j_lattice),
+        };
         let mut points = vec![];
-        let (mut new_i, mut new_j) = (self.0 as isize, self.1 as isize);
-        let limits = 0..50;
-        while limits.contains(&new_i) && limits.contains(&new_j) {
-            points.push(Point(new_i as usize, new_j as usize));
-            (new_i, new_j) = (new_i - i_min, new_j - j_min);
+        for (start, i_step, j_step) in [(self, i_step, j_step), (rhs, -i_step, -j_step)] {
+            for k in rule.multiples.clone() {
+                let Some((new_i, new_j)) = start.checked_step(k, i_step, j_step) else {
+                    break;
+                };
+                if !map.contains(new_i, new_j) {
+                    break;
+                }
+                points.push(Point(new_i as usize, new_j as usize));
+            }
+        }
+        if rule.interior {
+            (1..lattice_steps).for_each(|k| {
+                points.push(Point(
+                    (self.0 as isize - k * i_lattice) as usize,
+                    (self.1 as isize - k * j_lattice) as usize,
+                ))
+            });
+        }
+        points
+    }
+
+    fn checked_step(&self, k: usize, i_step: isize, j_step: isize) -> Option<(isize, isize)> {
+        let k = isize::try_from(k).ok()?;
+        Some((
+            (self.0 as isize).checked_add(k.checked_mul(i_step)?)?,
+            (self.1 as isize).checked_add(k.checked_mul(j_step)?)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+............
+........0...
+.....0......
+.......0....
+....0.......
+......A.....
+............
+............
+........A...
+.........A..
+............
+............";
+
+    #[test]
+    fn test_example() -> anyhow::Result<()> {
+        let map = read_antenna_map(EXAMPLE.as_bytes())?;
+        assert_eq!((map.height, map.width), (12, 12));
+        assert_eq!(map.antinodes(&PART_ONE).len(), 14);
+        assert_eq!(map.antinodes(&PART_TWO).len(), 34);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rules() -> anyhow::Result<()> {
+        let map = read_antenna_map("a.....\n......\n..a...\n......\n......\n......".as_bytes())?;
+        let antinodes = |multiples, unit, interior| {
+            map.antinodes(&AntinodeRule {
+                multiples,
+                unit,
+                interior,
+            })
+                .into_iter()
+                .map(|Point(i, j)| (i, j))
+                .sorted()
+                .collect::<Vec<_>>()
         };
-        (new_i, new_j) = (self.0 as isize + i_min, self.1 as isize + j_min);
-        while limits.contains(&new_i) && limits.contains(&new_j) {
-          points.push(Point(new_i as usize, new_j as usize));
-          (new_i, new_j) = (new_i + i_min, new_j + j_min);
-      };
-      points
+        let (distance, lattice) = (StepUnit::Distance, StepUnit::Lattice);
+        assert_eq!(antinodes(1..=1, distance, false), vec![(4, 4)]);
+        assert_eq!(antinodes(0..=2, distance, false), vec![(0, 0), (2, 2), (4, 4)]);
+        assert_eq!(antinodes(0..=0, lattice, true), vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(antinodes(1..=2, lattice, false), vec![(3, 3), (4, 4)]);
+        assert_eq!(antinodes(1..=2, lattice, true), vec![(1, 1), (3, 3), (4, 4)]);
+        assert_eq!(antinodes(1..=2, distance, true), vec![(1, 1), (4, 4)]);
+        assert_eq!(antinodes(0..=usize::MAX, lattice, false).len(), 5);
+        assert_eq!(antinodes(0..=usize::MAX, lattice, true).len(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() -> anyhow::Result<()> {
+        let map = read_antenna_map(EXAMPLE.as_bytes())?;
+        let expected = "\
+......#....#
+...#....0...
+....#0....#.
+..#....0....
+....0....#..
+.#....A.....
+...#........
+#......#....
+........A...
+.........A..
+..........#.
+..........#.";
+        assert_eq!(map.render(&map.antinodes(&PART_ONE)), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frequency_reports() -> anyhow::Result<()> {
+        let map = read_antenna_map(EXAMPLE.as_bytes())?;
+        let reports = frequency_reports(&map.antinodes_by_frequency(&PART_ONE));
+        let summary: Vec<_> = reports
+            .iter()
+            .map(|r| (r.frequency, r.antinodes, r.overlaps.len()))
+            .collect();
+        assert_eq!(summary, vec![('0', 10, 1), ('A', 5, 1)]);
+        assert_eq!(reports[1].overlaps.get(&(1, 3)), Some(&vec!['0']));
+        Ok(())
+    }
+
+    #[test]
+    fn test_input() -> anyhow::Result<()> {
+        assert_eq!(count_antinodes()?, 280);
+        assert_eq!(count_resonating_antinodes()?, 958);
+        Ok(())
     }
 }
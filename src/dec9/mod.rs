@@ -1,6 +1,12 @@
-use std::{cmp::Ordering, fs::read_to_string};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BTreeMap,
+    fmt,
+    fs::read_to_string,
+};
 
 use anyhow::{Context, Ok};
+use itertools::Itertools;
 
 const PATH: &str = "src/dec9/input.txt";
 
@@ -61,6 +67,14 @@ pub(crate) fn compact_checksum() -> anyhow::Result<usize> {
 
 pub(crate) fn unfragmented_compact_checksum() -> anyhow::Result<usize> {
     let file = read_to_string(PATH)?;
+    Ok(unfragmented_checksum(&file))
+}
+
+// Pointers to the first free span of at least each size. They only ever move forward, so
+// finding a span for every file is linear overall. Per-size min-heaps bound each lookup to
+// O(log n) instead, but measured 2-3x slower, so they were left out and only live on in
+// benches/dec9_benchmark.rs for comparison.
+pub(crate) fn unfragmented_checksum(file: &str) -> usize {
     let num_chars = file.chars().count();
     let n_spaces = num_chars / 2;
     let mut spaces = Vec::with_capacity(n_spaces);
@@ -83,14 +97,13 @@ pub(crate) fn unfragmented_compact_checksum() -> anyhow::Result<usize> {
             }
             pos + n
         });
-    pos_rev -= 1;
-    let checksum = file
-        .chars()
+    pos_rev = pos_rev.saturating_sub(1);
+    file.chars()
         .map(|c| c.to_digit(10).unwrap_or_default() as usize)
         .rev()
         .zip((0..num_chars).rev())
         .fold(0, |mut checksum, (n_rev, i_rev)| {
-            if i_rev % 2 == 0 {
+            if i_rev % 2 == 0 && n_rev > 0 {
                 update_ptr(&mut space_ptrs, &spaces, n_rev);
                 if let Some(space_ptr) = &space_ptrs[n_rev - 1] {
                     let (space_size, space_pos) = &mut spaces[*space_ptr];
@@ -99,16 +112,15 @@ pub(crate) fn unfragmented_compact_checksum() -> anyhow::Result<usize> {
                         *space_size -= n_rev;
                         *space_pos += n_rev;
                     } else {
-                        checksum += ((2 * pos_rev - n_rev + 1) * n_rev * i_rev) / 4;
+                        checksum += ((2 * pos_rev + 1 - n_rev) * n_rev * i_rev) / 4;
                     }
                 } else {
-                    checksum += ((2 * pos_rev - n_rev + 1) * n_rev * i_rev) / 4;
+                    checksum += ((2 * pos_rev + 1 - n_rev) * n_rev * i_rev) / 4;
                 }
             }
             pos_rev = pos_rev.saturating_sub(n_rev);
             checksum
-        });
-    Ok(checksum)
+        })
 }
 
 fn update_ptr(space_ptrs: &mut [Option<usize>; 9], spaces: &[(usize, usize)], to_update: usize) {
@@ -127,3 +139,351 @@ fn update_ptr(space_ptrs: &mut [Option<usize>; 9], spaces: &[(usize, usize)], to
         .find(|(_, (space_size, _))| *space_size >= to_update)
         .map(|(i, _)| i);
 }
+
+// Block by block model of the disk, slower than the checksums above but easy to inspect.
+// Each block holds the id of the file it belongs to, if any.
+#[derive(Clone)]
+pub(crate) struct DiskMap {
+    blocks: Vec<Option<usize>>,
+}
+
+// `Blocks` is part one and `FirstFit` is part two. The other whole file strategies differ in
+// which free span is picked, always to the left of the file, or in the order files are visited.
+// `Repeated` keeps running first fit passes until no file can move any further.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum CompactionStrategy {
+    Blocks,
+    FirstFit,
+    BestFit,
+    WorstFit,
+    IncreasingIds,
+    Repeated,
+}
+
+impl CompactionStrategy {
+    pub(crate) const ALL: [CompactionStrategy; 6] = [
+        Self::Blocks,
+        Self::FirstFit,
+        Self::BestFit,
+        Self::WorstFit,
+        Self::IncreasingIds,
+        Self::Repeated,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Fit {
+    First,
+    Best,
+    Worst,
+}
+
+// Free gaps counts the free blocks left before the last used block.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CompactionReport {
+    pub(crate) checksum: usize,
+    pub(crate) moves: usize,
+    pub(crate) free_gaps: usize,
+    pub(crate) fragmented_files: usize,
+}
+
+impl fmt::Display for CompactionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "checksum {}, {} moves, {} free gaps, {} fragmented files",
+            self.checksum, self.moves, self.free_gaps, self.fragmented_files
+        )
+    }
+}
+
+impl DiskMap {
+    pub(crate) fn parse(dense: &str) -> Self {
+        let mut blocks = vec![];
+        dense
+            .chars()
+            .filter_map(|c| c.to_digit(10))
+            .enumerate()
+            .for_each(|(i, n)| {
+                let block = if i % 2 == 0 { Some(i / 2) } else { None };
+                blocks.extend(std::iter::repeat_n(block, n as usize));
+            });
+        DiskMap { blocks }
+    }
+
+    pub(crate) fn compact(&mut self, strategy: CompactionStrategy) -> CompactionReport {
+        let file_count = self.files().len();
+        let moves = match strategy {
+            CompactionStrategy::Blocks => self.compact_blocks(),
+            CompactionStrategy::FirstFit => self.move_files((0..file_count).rev(), Fit::First),
+            CompactionStrategy::BestFit => self.move_files((0..file_count).rev(), Fit::Best),
+            CompactionStrategy::WorstFit => self.move_files((0..file_count).rev(), Fit::Worst),
+            CompactionStrategy::IncreasingIds => self.move_files(0..file_count, Fit::First),
+            CompactionStrategy::Repeated => {
+                let mut moves = 0;
+                loop {
+                    match self.move_files((0..file_count).rev(), Fit::First) {
+                        0 => break moves,
+                        pass_moves => moves += pass_moves,
+                    }
+                }
+            }
+        };
+        let last_used = self
+            .blocks
+            .iter()
+            .rposition(Option::is_some)
+            .unwrap_or_default();
+        CompactionReport {
+            checksum: self.checksum(),
+            moves,
+            free_gaps: self.blocks[..last_used]
+                .iter()
+                .filter(|b| b.is_none())
+                .count(),
+            fragmented_files: self
+                .runs()
+                .into_iter()
+                .counts_by(|(id, _, _)| id)
+                .values()
+                .filter(|&&runs| runs > 1)
+                .count(),
+        }
+    }
+
+    pub(crate) fn compact_blocks(&mut self) -> usize {
+        let (mut left, mut right) = (0, self.blocks.len());
+        let mut moves = 0;
+        loop {
+            while left < right && self.blocks[left].is_some() {
+                left += 1;
+            }
+            while left < right && self.blocks[right - 1].is_none() {
+                right -= 1;
+            }
+            if left + 1 >= right {
+                return moves;
+            }
+            self.blocks.swap(left, right - 1);
+            moves += 1;
+        }
+    }
+
+    // Files are visited once, in decreasing id order, and moved to the leftmost free span that
+    // fits them, if it is to the left of the file.
+    pub(crate) fn compact_files(&mut self) -> usize {
+        let file_count = self.files().len();
+        self.move_files((0..file_count).rev(), Fit::First)
+    }
+
+    fn move_files<I: Iterator<Item = usize>>(&mut self, ids: I, fit: Fit) -> usize {
+        let files = self.files();
+        let mut free_spans: BTreeMap<usize, usize> = self.free_spans().into_iter().collect();
+        let mut moves = 0;
+        for id in ids {
+            let (start, len) = files[id];
+            let mut candidates = free_spans
+                .range(..start)
+                .filter(|(_, &span_len)| span_len >= len)
+                .map(|(&span_start, &span_len)| (span_start, span_len));
+            let span = match fit {
+                Fit::First => candidates.next(),
+                Fit::Best => {
+                    candidates.min_by_key(|&(span_start, span_len)| (span_len, span_start))
+                }
+                Fit::Worst => {
+                    candidates.max_by_key(|&(span_start, span_len)| (span_len, Reverse(span_start)))
+                }
+            };
+            let Some((span_start, span_len)) = span else {
+                continue;
+            };
+            free_spans.remove(&span_start);
+            if span_len > len {
+                free_spans.insert(span_start + len, span_len - len);
+            }
+            release_span(&mut free_spans, start, len);
+            self.blocks[span_start..span_start + len].fill(Some(id));
+            self.blocks[start..start + len].fill(None);
+            moves += 1;
+        }
+        moves
+    }
+
+    pub(crate) fn checksum(&self) -> usize {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(pos, block)| block.map(|id| pos * id))
+            .sum()
+    }
+
+    // (id, start, length) of each run of blocks of the same file, from left to right.
+    fn runs(&self) -> Vec<(usize, usize, usize)> {
+        let mut runs: Vec<(usize, usize, usize)> = vec![];
+        self.blocks.iter().enumerate().for_each(|(pos, block)| {
+            if let Some(id) = *block {
+                match runs.last_mut() {
+                    Some((last_id, start, len)) if *last_id == id && *start + *len == pos => {
+                        *len += 1
+                    }
+                    _ => runs.push((id, pos, 1)),
+                }
+            }
+        });
+        runs
+    }
+
+    // (start, length) of each file indexed by id, assuming files are not fragmented.
+    fn files(&self) -> Vec<(usize, usize)> {
+        let runs = self.runs();
+        let mut files = vec![
+            (0, 0);
+            runs.iter()
+                .map(|(id, _, _)| id + 1)
+                .max()
+                .unwrap_or_default()
+        ];
+        runs.into_iter()
+            .for_each(|(id, start, len)| files[id] = (start, len));
+        files
+    }
+
+    // (start, length) of each run of free blocks, from left to right.
+    fn free_spans(&self) -> Vec<(usize, usize)> {
+        let mut spans: Vec<(usize, usize)> = vec![];
+        self.blocks.iter().enumerate().for_each(|(pos, block)| {
+            if block.is_none() {
+                match spans.last_mut() {
+                    Some((start, len)) if *start + *len == pos => *len += 1,
+                    _ => spans.push((pos, 1)),
+                }
+            }
+        });
+        spans
+    }
+}
+
+// Adds the blocks left behind by a moved file, merged with the free spans around them.
+fn release_span(free_spans: &mut BTreeMap<usize, usize>, mut start: usize, mut len: usize) {
+    if let Some((&prev_start, &prev_len)) = free_spans.range(..start).next_back() {
+        if prev_start + prev_len == start {
+            free_spans.remove(&prev_start);
+            start = prev_start;
+            len += prev_len;
+        }
+    }
+    if let Some(next_len) = free_spans.remove(&(start + len)) {
+        len += next_len;
+    }
+    free_spans.insert(start, len);
+}
+
+// Only the last digit of each id is shown, so layouts with more than ten files are ambiguous.
+impl fmt::Display for DiskMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.blocks.iter().try_for_each(|block| match block {
+            Some(id) => write!(f, "{}", id % 10),
+            None => write!(f, "."),
+        })
+    }
+}
+
+pub(crate) fn read_disk_map() -> anyhow::Result<DiskMap> {
+    Ok(DiskMap::parse(&read_to_string(PATH)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "2333133121414131402";
+
+    #[test]
+    fn test_compact_blocks() {
+        let mut disk = DiskMap::parse(EXAMPLE);
+        assert_eq!(disk.to_string(), "00...111...2...333.44.5555.6666.777.888899");
+        disk.compact_blocks();
+        assert_eq!(disk.to_string(), "0099811188827773336446555566..............");
+        assert_eq!(disk.checksum(), 1928);
+    }
+
+    #[test]
+    fn test_compact_files() {
+        let mut disk = DiskMap::parse(EXAMPLE);
+        disk.compact_files();
+        assert_eq!(disk.to_string(), "00992111777.44.333....5555.6666.....8888..");
+        assert_eq!(disk.checksum(), 2858);
+    }
+
+    #[test]
+    fn test_compaction_strategies() {
+        let compact = |dense: &str, strategy| {
+            let mut disk = DiskMap::parse(dense);
+            let report = disk.compact(strategy);
+            let mut blocks = disk.blocks.iter().flatten().copied().collect_vec();
+            blocks.sort_unstable();
+            let original = DiskMap::parse(dense)
+                .blocks
+                .into_iter()
+                .flatten()
+                .collect_vec();
+            assert_eq!(blocks, original, "{:?}", strategy);
+            (disk.to_string(), report)
+        };
+        let report = |checksum, moves, free_gaps, fragmented_files| CompactionReport {
+            checksum,
+            moves,
+            free_gaps,
+            fragmented_files,
+        };
+        assert_eq!(
+            compact(EXAMPLE, CompactionStrategy::Blocks).1,
+            report(1928, 12, 0, 2)
+        );
+        assert_eq!(
+            compact(EXAMPLE, CompactionStrategy::FirstFit).1,
+            report(2858, 4, 12, 0)
+        );
+
+        // Every strategy leaves this disk map in a different layout.
+        let expected = [
+            ("005541113323.........", report(163, 6, 0, 1)),
+            ("005541112...333......", report(192, 3, 3, 0)),
+            ("003331115524.........", report(194, 3, 0, 0)),
+            ("005521114...333......", report(200, 3, 3, 0)),
+            ("001112333455.........", report(223, 5, 0, 0)),
+            ("005541112333.........", report(165, 4, 0, 0)),
+        ];
+        CompactionStrategy::ALL
+            .iter()
+            .zip(expected)
+            .for_each(|(&strategy, (layout, report))| {
+                assert_eq!(
+                    compact("23321132112", strategy),
+                    (layout.to_string(), report),
+                    "{:?}",
+                    strategy
+                );
+            });
+    }
+
+    #[test]
+    fn test_unfragmented_checksum() {
+        assert_eq!(unfragmented_checksum(EXAMPLE), 2858);
+        assert_eq!(unfragmented_checksum("10101"), 5);
+        assert_eq!(unfragmented_checksum("1210201"), 23);
+    }
+
+    #[test]
+    fn test_model_matches_checksums() -> anyhow::Result<()> {
+        let mut disk = read_disk_map()?;
+        disk.compact_blocks();
+        assert_eq!(disk.checksum(), compact_checksum()?);
+
+        let mut disk = read_disk_map()?;
+        disk.compact_files();
+        assert_eq!(disk.checksum(), unfragmented_compact_checksum()?);
+        Ok(())
+    }
+}
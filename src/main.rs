@@ -15,11 +15,28 @@ mod dec14;
 mod dec15;
 mod dec16;
 
+use dec7::Operation::{BaseUnion, Multiplication, Subtraction, Sum};
+
 fn main() -> anyhow::Result<()> {
-    println!("diff {}", dec1::diff());
-    println!("Similarity score {}", dec1::similarity_score());
-    println!("Safe reports {}", dec2::safe_count());
-    println!("Dampened safe reports {}", dec2::dampened_count());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("antinodes") => return print_antinodes(args.iter().any(|arg| arg == "--resonating")),
+        Some("easter-egg") => return print_easter_egg(args.iter().any(|arg| arg == "--safety-factor")),
+        Some("export") => return export_frames(&args[1..]),
+        Some("warehouse") => return print_warehouse_steps(&args[1..]),
+        Some("reports") => return print_reports(&args[1..]),
+        Some("stream") => return print_streamed_location_lists(&args[1..]),
+        Some("disagreement") => return print_disagreement(&args[1..]),
+        Some(command) => anyhow::bail!("Unknown command {}", command),
+        None => (),
+    }
+
+    println!("diff {}", dec1::diff()?);
+    println!("Similarity score {}", dec1::similarity_score()?);
+    println!("Similarity score counting left ids once {}", dec1::read_location_lists()?.similarity_score(dec1::DuplicateWeight::Once));
+    println!("Safe reports {}", dec2::safe_count()?);
+    println!("Dampened safe reports {}", dec2::dampened_count()?);
+    println!("Safe reports with two removable levels {}", dec2::count_safe(&dec2::ReportPolicy { max_removed: 2, ..dec2::DAMPENED })?);
     println!("Safe mul {}", dec3::sum_mul(false)?);
     println!("Safe enabled mul {}", dec3::sum_mul(true)?);
     println!("XMAS count {}", dec4::count_xmas()?);
@@ -28,14 +45,43 @@ fn main() -> anyhow::Result<()> {
     println!("Middle page ordered updates sum by predecessors and antecessors struct {}", dec5::middle_page_sum(true)?);
     println!("Middle page unordered updates sum by predecessors and antecessors struct {}", dec5::middle_page_sum(false)?);
     println!("Middle page ordered updates sum by custom ordering function {}", dec5::page_custom_order(true)?);
-    println!("Middle page unordered updates sum by custom ordering function {}", dec5::page_custom_order(true)?);
+    println!("Middle page unordered updates sum by custom ordering function {}", dec5::page_custom_order(false)?);
+    println!("Middle page ordered updates sum by topological sort {}", dec5::middle_page_topological_sum(true)?);
+    println!("Middle page unordered updates sum by topological sort {}", dec5::middle_page_topological_sum(false)?);
+    let diagnostics = dec5::explain_unordered_updates()?;
+    println!("Rule violations in unordered updates {}", diagnostics.iter().map(|d| d.violations.len()).sum::<usize>());
+    println!("Page moves to fix unordered updates {}", diagnostics.iter().filter_map(|d| d.moves.as_ref().ok()).map(Vec::len).sum::<usize>());
+    match dec5::find_disagreement(dec5::RuleSet::Total, 1000, 2024) {
+        Some(disagreement) => println!("{}", disagreement),
+        None => println!("Page ordering strategies agree on random inputs"),
+    }
     println!("Guard unique position count {}", dec6::guard_position_count()?);
     println!("Possible loops with new obstacle {}", dec6::count_possible_loops()?);
-    println!("Feasible equations sum {}", dec7::feasible_equations()?);
+    println!("Feasible equations sum {}", dec7::feasible_equations::<usize>(dec7::PART_ONE)?);
+    println!("Feasible equations sum with union {}", dec7::feasible_equations::<usize>(dec7::PART_TWO)?);
+    println!("Feasible equations sum with union by reverse search {}", dec7::feasible_equations_reverse::<usize>(dec7::PART_TWO)?);
+    println!("Feasible equations sum with subtraction {}", dec7::feasible_equations::<usize>(&[Sum, Multiplication, Subtraction])?);
+    println!("Feasible equations sum with binary union {}", dec7::feasible_equations::<usize>(&[Sum, Multiplication, BaseUnion(2)])?);
+    let equations = dec7::read_equations::<usize>()?;
+    if let Some(solution) = equations.iter().find_map(|e| e.solutions(dec7::PART_TWO, true).into_iter().next()) {
+        println!("First feasible equation {}", solution);
+    }
+    println!("Feasible equation solutions count {}", equations.iter().map(|e| e.solution_count(dec7::PART_TWO)).sum::<usize>());
     println!("Antinodes count {}", dec8::count_antinodes()?);
     println!("Resonating antinodes count {}", dec8::count_resonating_antinodes()?);
+    println!("Antinodes count up to three distances away {}", dec8::count_antinodes_with(&dec8::AntinodeRule { multiples: 1..=3, unit: dec8::StepUnit::Distance, interior: false })?);
     println!("Compact checksum {}", dec9::compact_checksum()?);
     println!("Unfragmented compact checksum {}", dec9::unfragmented_compact_checksum()?);
+    let mut disk = dec9::read_disk_map()?;
+    disk.compact_blocks();
+    println!("Compact checksum by disk model {}", disk.checksum());
+    let mut disk = dec9::read_disk_map()?;
+    disk.compact_files();
+    println!("Unfragmented compact checksum by disk model {}", disk.checksum());
+    let disk = dec9::read_disk_map()?;
+    for strategy in dec9::CompactionStrategy::ALL {
+        println!("{:?} compaction {}", strategy, disk.clone().compact(strategy));
+    }
     println!("Total trail score {}", dec10::sum_trailhead_scores(true)?);
     println!("Total trail rating {}", dec10::sum_trailhead_scores(false)?);
     println!("Stone count {}", dec11::count_stones()?);
@@ -44,8 +90,97 @@ fn main() -> anyhow::Result<()> {
     println!("Minimum tokens {}", dec13::minimum_tokens(0)?);
     println!("Minimum tokens with offset {}", dec13::minimum_tokens(10000000000000)?);
     println!("Safety factor {}", dec14::safety_factor()?);
+    println!("Easter egg second {}", dec14::find_easter_egg(dec14::Detector::Variance)?.second);
+    let analysis = dec14::analyze_periods()?;
+    println!("Longest robot period {}", analysis.robot_periods.iter().max().unwrap_or(&0));
+    println!("Robots system period {}", analysis.system_period);
+    println!("Seconds with overlapping robots {}", analysis.overlap_seconds.len());
+    println!("Distinct robot configurations {}", analysis.distinct_configurations);
     println!("Good positioning system sum {}", dec15::sum_box_positions()?);
     println!("Good positioning system wide {}", dec15::sum_wide_box_positions()?);
     println!("Lowest path score {}", dec16::lowest_score_path()?);
     Ok(())
 }
+
+fn print_antinodes(resonating: bool) -> anyhow::Result<()> {
+    let rule = if resonating { dec8::PART_TWO } else { dec8::PART_ONE };
+    println!("{}", dec8::render_antinodes(&rule)?);
+    for report in dec8::antinodes_per_frequency(&rule)? {
+        println!("{}", report);
+    }
+    Ok(())
+}
+
+fn print_easter_egg(by_safety_factor: bool) -> anyhow::Result<()> {
+    let detector = if by_safety_factor { dec14::Detector::SafetyFactor } else { dec14::Detector::Variance };
+    let easter_egg = dec14::find_easter_egg(detector)?;
+    println!("{}", easter_egg.frame);
+    println!("Easter egg second {}", easter_egg.second);
+    Ok(())
+}
+
+fn export_frames(args: &[String]) -> anyhow::Result<()> {
+    let [format, start, end, directory] = args else {
+        anyhow::bail!("Usage: export <text|pgm|gif> <start> <end> <directory>");
+    };
+    let paths = dec14::export_frames(start.parse()?..end.parse()?, directory.as_ref(), format.parse()?)?;
+    println!("Wrote {} files to {}", paths.len(), directory);
+    Ok(())
+}
+
+// Steps are either single moves like `12` or ranges like `10..20`, dumped in the order given.
+fn print_warehouse_steps(args: &[String]) -> anyhow::Result<()> {
+    let scale = if args.iter().any(|arg| arg == "--wide") { 2 } else { 1 };
+    let mut warehouse = dec15::read_warehouse(scale)?;
+    for steps in args.iter().filter(|arg| *arg != "--wide") {
+        let (start, end) = match steps.split_once("..") {
+            Some((start, end)) => (start.parse::<usize>()?, end.parse()?),
+            None => (steps.parse()?, steps.parse::<usize>()? + 1),
+        };
+        for state in warehouse.replay(start).take(end.saturating_sub(start)) {
+            match state.record {
+                Some(record) => println!("Step {}: {}", state.step, record),
+                None => println!("Step {}", state.step),
+            }
+            println!("{}", state.grid);
+        }
+    }
+    // Replayed moves would be logged again, so the summary comes from a single run.
+    let mut warehouse = dec15::read_warehouse(scale)?.with_log();
+    warehouse.run();
+    let log = warehouse.log().unwrap_or_default();
+    let moved = log.iter().filter(|record| record.moved).count();
+    let pushed: usize = log.iter().map(|record| record.boxes.len()).sum();
+    println!("{} of {} simulated moves made, {} boxes pushed", moved, log.len(), pushed);
+    Ok(())
+}
+
+fn print_reports(args: &[String]) -> anyhow::Result<()> {
+    let policy = if args.iter().any(|arg| arg == "--dampened") { dec2::DAMPENED } else { dec2::STRICT };
+    let format = args.iter().find(|arg| *arg != "--dampened").map_or(Ok(dec2::ExportFormat::Csv), |format| format.parse())?;
+    println!("{}", dec2::export_explanations(&dec2::explain_reports(&policy)?, format));
+    Ok(())
+}
+
+fn print_streamed_location_lists(args: &[String]) -> anyhow::Result<()> {
+    let Some(path) = args.first() else {
+        anyhow::bail!("Usage: stream <path> [chunk size]");
+    };
+    let chunk_size = args.get(1).map_or(Ok(1 << 20), |chunk_size| chunk_size.parse())?;
+    let totals = dec1::stream_location_lists(path.as_ref(), chunk_size)?;
+    println!("diff {}", totals.total_distance);
+    println!("Similarity score {}", totals.similarity_score);
+    Ok(())
+}
+
+fn print_disagreement(args: &[String]) -> anyhow::Result<()> {
+    let Some(rule_set) = args.first() else {
+        anyhow::bail!("Usage: disagreement <total|sparse|cyclic> [seed]");
+    };
+    let seed = args.get(1).map_or(Ok(2024), |seed| seed.parse())?;
+    match dec5::find_disagreement(rule_set.parse()?, 1000, seed) {
+        Some(disagreement) => println!("{}", disagreement),
+        None => println!("Page ordering strategies agree on random inputs"),
+    }
+    Ok(())
+}